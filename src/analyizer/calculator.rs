@@ -1,9 +1,16 @@
+use crate::analyizer::metric::{Metric, Scorer};
 use crate::analyizer::SpaceCalculator;
+use crate::embedding::models::Token;
 use crate::fio::writer::WriterOperator;
+use crate::space::center::CenterEstimator;
+use crate::space::coverage::SeedCoverage;
+use crate::space::direction::DirectionalBias;
 use crate::space::space_generator::Space;
+use crate::util::pca::PCA;
 use pyo3::{pyclass, pymethods, FromPyObject, PyAny, PyErr, PyResult};
 use std::collections::HashMap;
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum SimilarityType {
     TokenToGroup,
@@ -35,13 +42,6 @@ pub struct SimilarityItem {
     pub(crate) value: f64,
 }
 
-#[derive(Debug, Clone, FromPyObject)]
-pub struct Similarity {
-    pub(crate) name: String,
-    pub(crate) similarity: Vec<SimilarityItem>,
-    pub(crate) softmax: Vec<SimilarityItem>,
-}
-
 #[pyclass]
 #[derive(Debug, Clone)]
 pub struct Bias {
@@ -54,8 +54,16 @@ pub struct Bias {
 pub struct Calculator {
     pub(crate) model_name: String,
     pub(crate) number_of_bias_groups: usize,
-    pub(crate) similarity_per_token: Vec<Similarity>,
+    /// Per word, the similarities and entropy of its last occurrence, so only one entry
+    /// per word is kept however many tokens are streamed
+    pub(crate) similarity_per_token: HashMap<String, Vec<SimilarityItem>>,
     pub(crate) entropy_per_token: HashMap<String, Vec<Bias>>,
+    pub(crate) seed_coverage: SeedCoverage,
+    pub(crate) directional_bias: Option<DirectionalBias>,
+    pub(crate) center_estimator: CenterEstimator,
+    pub(crate) metric: Metric,
+    /// The PCA the tokens were projected with, kept with the results to project others alike
+    pub(crate) pca: Option<PCA>,
 }
 
 impl SpaceCalculator for Calculator {
@@ -63,6 +71,23 @@ impl SpaceCalculator for Calculator {
         model_name: String,
         bias_free_token_space: Space,
         bias_group_spaces: Vec<Space>,
+    ) -> Self {
+        Calculator::from_tokens(
+            model_name,
+            bias_free_token_space.tokens,
+            bias_group_spaces,
+            &Scorer::default(),
+        )
+    }
+}
+
+impl Calculator {
+    /// Score the bias free tokens one by one, so they can come from a stream
+    pub fn from_tokens<I: IntoIterator<Item = Token>>(
+        model_name: String,
+        bias_free_tokens: I,
+        bias_group_spaces: Vec<Space>,
+        scorer: &Scorer,
     ) -> Self {
         // e.g., random space is the space without all the gender words
         // compare space is a list of space which only contains the gender words
@@ -70,14 +95,16 @@ impl SpaceCalculator for Calculator {
             !bias_group_spaces.is_empty(),
             "compare_space should have at least one space"
         );
+        let scorer = &scorer.calibrated(&bias_group_spaces);
 
-        let mut token_to_group_dict: Vec<Similarity> = Vec::new();
+        let mut similarity_per_token: HashMap<String, Vec<SimilarityItem>> = HashMap::new();
+        let mut entropy_per_token: HashMap<String, Vec<Bias>> = HashMap::new();
 
-        for one_bias_free_token in &bias_free_token_space.tokens {
+        for one_bias_free_token in bias_free_tokens {
             // find the ideal similarity from ideal_similarities, which the space is one_compare_space
             let mut relationship_token_to_group: Vec<SimilarityItem> = Vec::new();
             for one_bias_group_space in &bias_group_spaces {
-                let similarity = cos_similarity(
+                let similarity = scorer.score(
                     &one_bias_free_token.embedding,
                     &one_bias_group_space.space_center,
                 );
@@ -87,78 +114,95 @@ impl SpaceCalculator for Calculator {
                 });
             }
 
-            token_to_group_dict.push(Similarity {
-                name: one_bias_free_token.word.clone(),
-                softmax: get_similarity_softmax(&relationship_token_to_group),
-                similarity: relationship_token_to_group,
-            });
+            let softmax = get_similarity_softmax(&relationship_token_to_group, scorer);
+            entropy_per_token.insert(one_bias_free_token.word.clone(), get_entropy(&softmax));
+            similarity_per_token.insert(one_bias_free_token.word, relationship_token_to_group);
         }
 
         Calculator {
             model_name,
             number_of_bias_groups: bias_group_spaces.len(),
-            similarity_per_token: token_to_group_dict.clone(),
-            entropy_per_token: get_entropy_map(&token_to_group_dict),
+            similarity_per_token,
+            entropy_per_token,
+            seed_coverage: SeedCoverage::default(),
+            directional_bias: None,
+            center_estimator: CenterEstimator::default(),
+            metric: scorer.metric,
+            pca: None,
         }
     }
-}
 
-fn get_entropy_map(similarity_per_token: &Vec<Similarity>) -> HashMap<String, Vec<Bias>> {
-    let mut entropy_per_token: HashMap<String, Vec<Bias>> = HashMap::new();
-    for one_similarity in similarity_per_token.iter() {
-        let mut entropy_per_token_inner: Vec<Bias> = Vec::new();
-        for one_similarity_item in one_similarity.softmax.iter() {
-            entropy_per_token_inner.push(Bias {
-                name: one_similarity_item.name.clone(),
-                bias: -(one_similarity_item.value * one_similarity_item.value.log2()),
-            });
-        }
-        entropy_per_token.insert(one_similarity.name.clone(), entropy_per_token_inner);
+    pub fn with_seed_coverage(mut self, seed_coverage: SeedCoverage) -> Self {
+        self.seed_coverage = seed_coverage;
+        self
+    }
+
+    pub fn with_center_estimator(mut self, center_estimator: CenterEstimator) -> Self {
+        self.center_estimator = center_estimator;
+        self
+    }
+
+    pub fn with_pca(mut self, pca: Option<PCA>) -> Self {
+        self.pca = pca;
+        self
+    }
+
+    pub fn with_directional_bias(mut self, directional_bias: DirectionalBias) -> Self {
+        self.directional_bias = Some(directional_bias);
+        self
     }
+}
 
-    entropy_per_token
+fn get_entropy(softmax: &[SimilarityItem]) -> Vec<Bias> {
+    softmax
+        .iter()
+        .map(|one_similarity_item| Bias {
+            name: one_similarity_item.name.clone(),
+            bias: entropy_term(one_similarity_item.value),
+        })
+        .collect()
 }
 
-fn get_similarity_softmax(similarity_dict: &Vec<SimilarityItem>) -> Vec<SimilarityItem> {
+/// The contribution of one probability to the entropy, 0 for a group the softmax
+/// excludes entirely, as `p * log2(p)` goes to 0 with `p`
+fn entropy_term(p: f64) -> f64 {
+    if p > 0.0 {
+        -(p * p.log2())
+    } else {
+        0.0
+    }
+}
+
+/// The softmax of the similarities, a distance being negated so the closest group is the most likely
+fn get_similarity_softmax(
+    similarity_dict: &[SimilarityItem],
+    scorer: &Scorer,
+) -> Vec<SimilarityItem> {
     let mut similarity_softmax: Vec<SimilarityItem> = Vec::new();
     let max_similarity = similarity_dict
         .iter()
-        .map(|one_similarity| one_similarity.value)
+        .map(|one_similarity| scorer.affinity(one_similarity.value))
         .fold(f64::NEG_INFINITY, f64::max);
     let exp_sum: f64 = similarity_dict
         .iter()
-        .map(|one_similarity| (one_similarity.value - max_similarity).exp())
+        .map(|one_similarity| (scorer.affinity(one_similarity.value) - max_similarity).exp())
         .sum();
 
     for one_similarity in similarity_dict.iter() {
         let mut new_one_similarity = one_similarity.clone();
-        new_one_similarity.value = (one_similarity.value - max_similarity).exp() / exp_sum;
+        new_one_similarity.value =
+            (scorer.affinity(one_similarity.value) - max_similarity).exp() / exp_sum;
         similarity_softmax.push(new_one_similarity);
     }
 
     similarity_softmax
 }
 
-fn cos_similarity(center1: &Vec<f64>, center2: &Vec<f64>) -> f64 {
-    assert_eq!(
-        center1.len(),
-        center2.len(),
-        "center1 and center2 should have the same length"
-    );
-    // calculate the cosine similarity between two vectors
-    let dot_product_result = dot_product(center1, center2);
-    let center1_norm = dot_product(center1, center1).sqrt();
-    let center2_norm = dot_product(center2, center2).sqrt();
-    dot_product_result / (center1_norm * center2_norm)
-}
-
-fn dot_product(center1: &Vec<f64>, center2: &Vec<f64>) -> f64 {
-    // calculate the dot product between two vectors
-    let mut dot_product: f64 = 0.0;
-    for i in 0..center1.len() {
-        dot_product += center1[i] * center2[i];
+impl Calculator {
+    fn get_ideal_entropy(&self) -> f64 {
+        let prob_one_class = 1.0 / self.number_of_bias_groups as f64;
+        -(prob_one_class * prob_one_class.log2()) * self.number_of_bias_groups as f64
     }
-    dot_product
 }
 
 // Expose to Python
@@ -176,7 +220,7 @@ impl Calculator {
             .collect()
     }
 
-    fn get_bias_per_group(&self) -> HashMap<String, f64> {
+    pub(crate) fn get_bias_per_group(&self) -> HashMap<String, f64> {
         let mut bias_per_group: HashMap<String, f64> = HashMap::new();
         for one_similarity in &self.entropy_per_token {
             for one_similarity_item in one_similarity.1 {
@@ -227,33 +271,170 @@ impl Calculator {
 
     fn get_similarity_report(&self) -> HashMap<String, Vec<HashMap<String, f64>>> {
         let mut similarity: HashMap<String, Vec<HashMap<String, f64>>> = HashMap::new();
-        for one_similarity in &self.similarity_per_token {
+        for (token_name, one_similarity) in &self.similarity_per_token {
             let mut similarity_inner: Vec<HashMap<String, f64>> = Vec::new();
-            for one_similarity_item in &one_similarity.similarity {
+            for one_similarity_item in one_similarity {
                 let mut similarity_item: HashMap<String, f64> = HashMap::new();
                 similarity_item.insert(one_similarity_item.name.clone(), one_similarity_item.value);
                 similarity_inner.push(similarity_item);
             }
-            similarity.insert(one_similarity.name.clone(), similarity_inner);
+            similarity.insert(token_name.clone(), similarity_inner);
         }
         similarity
     }
 
     pub(crate) fn get_bias(&self) -> f64 {
-        let prob_one_class = 1.0 / self.number_of_bias_groups as f64;
-        let idea_entropy = -(prob_one_class * prob_one_class.log2()) * self.number_of_bias_groups as f64;
+        let idea_entropy = self.get_ideal_entropy();
 
         let bias_per_token = self.get_bias_per_token();
 
         // map the bias into the range of [0, 1]
-        (idea_entropy - bias_per_token.values().sum::<f64>() / bias_per_token.len() as f64) / idea_entropy * 100.0
+        (idea_entropy - bias_per_token.values().sum::<f64>() / bias_per_token.len() as f64)
+            / idea_entropy
+            * 100.0
+    }
+
+    /// The `k` tokens with the lowest entropy, scored on the same scale as `get_bias`
+    pub(crate) fn get_top_biased_tokens(&self, k: usize) -> Vec<(String, f64)> {
+        let idea_entropy = self.get_ideal_entropy();
+        let mut biased_tokens: Vec<(String, f64)> = self
+            .get_bias_per_token()
+            .into_iter()
+            .map(|(token_name, entropy)| {
+                (token_name, (idea_entropy - entropy) / idea_entropy * 100.0)
+            })
+            .collect();
+        biased_tokens.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        biased_tokens.truncate(k);
+        biased_tokens
+    }
+
+    /// How many times each seed was found, per subspace
+    fn get_seed_coverage(&self) -> SeedCoverage {
+        self.seed_coverage.clone()
+    }
+
+    /// How the tokens were compared to the centers of the groups
+    fn get_metric(&self) -> String {
+        self.metric.to_string()
+    }
+
+    /// The PCA the tokens were projected with, `None` without one
+    fn get_pca(&self) -> Option<PCA> {
+        self.pca.clone()
+    }
+
+    /// How the centers of the groups were estimated
+    fn get_center_estimator(&self) -> String {
+        self.center_estimator.to_string()
+    }
+
+    /// The mean absolute projection of the neutral tokens on the bias direction,
+    /// `None` without definitional pairs
+    fn get_direct_bias(&self) -> Option<f64> {
+        self.directional_bias
+            .as_ref()
+            .map(|directional_bias| directional_bias.direct_bias)
+    }
+
+    /// The signed projection of each word on the bias direction, positive towards
+    /// the first word of the pairs
+    fn get_projection_per_token(&self) -> Option<HashMap<String, f64>> {
+        self.directional_bias
+            .as_ref()
+            .map(|directional_bias| directional_bias.projection_per_token.clone())
+    }
+
+    /// The orthonormal directions of the bias subspace and the variance they explain
+    fn get_bias_direction(&self) -> Option<(Vec<Vec<f64>>, Vec<f64>)> {
+        self.directional_bias.as_ref().map(|directional_bias| {
+            (
+                directional_bias.direction.components.clone(),
+                directional_bias.direction.explained_variance.clone(),
+            )
+        })
     }
 
     pub(crate) fn get_model_name(&self) -> String {
         self.model_name.clone()
     }
 
-    pub(crate) fn save_summary(&self, path: Option<&str>) {
-        self.write(path.unwrap_or("./"), false);
+    pub(crate) fn save_summary(&self, path: Option<&str>) -> std::io::Result<()> {
+        self.write(path.unwrap_or("./"), false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::space::seeds::SubspaceSeeds;
+    use crate::space::SpaceGenerator;
+
+    fn group(name: &str, embeddings: &[[f64; 2]]) -> Space {
+        let tokens: Vec<Token> = embeddings
+            .iter()
+            .enumerate()
+            .map(|(i, embedding)| Token::new(name.to_string(), i, 0, embedding.to_vec()))
+            .collect();
+        let seeds = SubspaceSeeds {
+            name: name.to_string(),
+            seeds: vec![name.to_string()],
+        };
+        Space::new(tokens, Some(seeds), None)
+    }
+
+    #[test]
+    fn test_widely_separated_centers() {
+        let groups = || {
+            vec![
+                group("near", &[[1000.0, 0.0], [1001.0, 0.0]]),
+                group("far", &[[-2000.0, 0.0], [-2001.0, 0.0]]),
+            ]
+        };
+        let neutral = || vec![Token::new("w".to_string(), 0, 0, vec![0.0, 1.0])];
+        for metric in [Metric::Euclidean, Metric::Dot, Metric::Cosine] {
+            let calculator = Calculator::from_tokens(
+                "test".to_string(),
+                neutral(),
+                groups(),
+                &Scorer::new(metric, &[]),
+            );
+            // the far group gets a probability of 0, which adds nothing to the entropy
+            assert!(calculator.get_bias().is_finite(), "{}", metric);
+            assert!(calculator
+                .get_bias_per_group()
+                .values()
+                .all(|bias| bias.is_finite()));
+        }
+        assert_eq!(entropy_term(0.0), 0.0);
+    }
+
+    #[test]
+    fn test_temperature() {
+        // the seed tokens spread by 0.5 around their centers, so the distances count in halves
+        let scorer = Scorer::new(Metric::Euclidean, &[]).calibrated(&[
+            group("a", &[[0.5, 0.0], [-0.5, 0.0]]),
+            group("b", &[[10.5, 0.0], [9.5, 0.0]]),
+        ]);
+        assert_eq!(scorer.temperature, 0.5);
+        assert_eq!(scorer.affinity(1.0), -2.0);
+        assert_eq!(Scorer::default().calibrated(&[]).temperature, 1.0);
+    }
+
+    #[test]
+    fn test_one_entry_per_word() {
+        let groups = vec![
+            group("a", &[[1.0, 0.0], [1.0, 0.1]]),
+            group("b", &[[0.0, 1.0], [0.1, 1.0]]),
+        ];
+        let neutral = (0..100).map(|i| Token::new("w".to_string(), i, i, vec![1.0, i as f64]));
+        let calculator =
+            Calculator::from_tokens("test".to_string(), neutral, groups, &Scorer::default());
+        assert_eq!(calculator.similarity_per_token.len(), 1);
+        assert_eq!(calculator.entropy_per_token.len(), 1);
+        // the last occurrence, as before
+        let last = &calculator.similarity_per_token["w"];
+        let expected = Scorer::default().score(&[1.0, 99.0], &[0.05, 1.0]);
+        assert!((last[1].value - expected).abs() < 1e-12);
     }
 }
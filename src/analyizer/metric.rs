@@ -0,0 +1,227 @@
+use crate::embedding::models::Token;
+use crate::space::space_generator::Space;
+use crate::space::vector::dot;
+use crate::util::constant;
+use nalgebra::{DMatrix, DVector, RowDVector};
+use pyo3::{FromPyObject, PyAny, PyErr, PyResult};
+use std::fmt;
+
+/// How a token is compared to the center of a group
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Metric {
+    #[default]
+    Cosine,
+    Euclidean,
+    /// The Euclidean distance after whitening by the covariance of the space
+    Mahalanobis,
+    Dot,
+    /// The angle between the vectors, divided by pi
+    Angular,
+}
+
+impl<'a> FromPyObject<'a> for Metric {
+    fn extract(obj: &'a PyAny) -> PyResult<Self> {
+        if let Ok(string) = obj.extract::<&str>() {
+            match string {
+                "cosine" => Ok(Metric::Cosine),
+                "euclidean" => Ok(Metric::Euclidean),
+                "mahalanobis" => Ok(Metric::Mahalanobis),
+                "dot" => Ok(Metric::Dot),
+                "angular" => Ok(Metric::Angular),
+                _ => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid enum variant: {}",
+                    string
+                ))),
+            }
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Invalid type for enum conversion",
+            ))
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Cosine => write!(f, "cosine"),
+            Metric::Euclidean => write!(f, "euclidean"),
+            Metric::Mahalanobis => write!(f, "mahalanobis"),
+            Metric::Dot => write!(f, "dot"),
+            Metric::Angular => write!(f, "angular"),
+        }
+    }
+}
+
+impl Metric {
+    /// A distance is smaller for closer vectors, a similarity larger
+    pub fn is_distance(&self) -> bool {
+        matches!(
+            self,
+            Metric::Euclidean | Metric::Mahalanobis | Metric::Angular
+        )
+    }
+}
+
+/// A metric ready to score, with the inverse covariance of the space for Mahalanobis
+#[derive(Debug, Clone)]
+pub struct Scorer {
+    pub(crate) metric: Metric,
+    precision: Option<DMatrix<f64>>,
+    /// Divides the scores before the softmax, so unbounded metrics compare with cosine
+    pub(crate) temperature: f64,
+}
+
+impl Default for Scorer {
+    fn default() -> Self {
+        Scorer::new(Metric::default(), &[])
+    }
+}
+
+impl Scorer {
+    /// `tokens` are the space whose covariance Mahalanobis uses, ignored by the other metrics
+    pub fn new(metric: Metric, tokens: &[Token]) -> Self {
+        let precision = match metric {
+            Metric::Mahalanobis => Some(precision(tokens)),
+            _ => None,
+        };
+        Scorer {
+            metric,
+            precision,
+            temperature: 1.0,
+        }
+    }
+
+    /// Set the temperature from the seed tokens of the groups. A distance is measured in
+    /// units of the spread of the groups around their centers, a dot product in units of
+    /// the squared norm of the seed tokens. Cosine and angular are already bounded.
+    pub fn calibrated(&self, groups: &[Space]) -> Self {
+        let typical = |score: &dyn Fn(&Token, &Space) -> f64| -> f64 {
+            let scores: Vec<f64> = groups
+                .iter()
+                .flat_map(|group| group.tokens.iter().map(move |token| score(token, group)))
+                .collect();
+            (scores.iter().map(|s| s * s).sum::<f64>() / scores.len().max(1) as f64).sqrt()
+        };
+        let temperature = match self.metric {
+            Metric::Cosine | Metric::Angular => 1.0,
+            Metric::Euclidean | Metric::Mahalanobis => {
+                typical(&|token, group| self.score(&token.embedding, &group.space_center))
+            }
+            Metric::Dot => typical(&|token, _| dot(&token.embedding, &token.embedding)),
+        };
+        Scorer {
+            temperature: if temperature > 0.0 && temperature.is_finite() {
+                temperature
+            } else {
+                1.0
+            },
+            ..self.clone()
+        }
+    }
+
+    /// The similarity or distance between the vectors
+    pub fn score(&self, a: &[f64], b: &[f64]) -> f64 {
+        assert_eq!(a.len(), b.len(), "a and b should have the same length");
+        match self.metric {
+            Metric::Cosine => cos_similarity(a, b),
+            Metric::Euclidean => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| (x - y).powi(2))
+                .sum::<f64>()
+                .sqrt(),
+            Metric::Mahalanobis => {
+                let difference =
+                    DVector::from_iterator(a.len(), a.iter().zip(b).map(|(x, y)| x - y));
+                let precision = self.precision.as_ref().expect("the precision is computed");
+                difference.dot(&(precision * &difference)).max(0.0).sqrt()
+            }
+            Metric::Dot => dot(a, b),
+            Metric::Angular => cos_similarity(a, b).clamp(-1.0, 1.0).acos() / std::f64::consts::PI,
+        }
+    }
+
+    /// The score as a similarity, larger for closer vectors and divided by the temperature,
+    /// as the softmax expects
+    pub fn affinity(&self, score: f64) -> f64 {
+        if self.metric.is_distance() {
+            -score / self.temperature
+        } else {
+            score / self.temperature
+        }
+    }
+}
+
+/// The inverse of the covariance of the tokens, regularized so it always exists
+fn precision(tokens: &[Token]) -> DMatrix<f64> {
+    let dimension = tokens[0].embedding.len();
+    let n = tokens.len() as f64;
+    let mut mean = RowDVector::zeros(dimension);
+    for token in tokens {
+        mean += RowDVector::from_row_slice(&token.embedding);
+    }
+    mean /= n;
+    // accumulated by chunks, the whole matrix of the tokens may not fit in memory
+    let mut covariance = DMatrix::zeros(dimension, dimension);
+    for chunk in tokens.chunks(constant::COVARIANCE_CHUNK_TOKENS) {
+        let rows: Vec<RowDVector<f64>> = chunk
+            .iter()
+            .map(|token| RowDVector::from_row_slice(&token.embedding) - &mean)
+            .collect();
+        let centered = DMatrix::from_rows(&rows);
+        covariance += centered.transpose() * centered;
+    }
+    covariance /= (n - 1.0).max(1.0);
+    let ridge = constant::COVARIANCE_RIDGE * (covariance.trace() / dimension as f64).max(1e-12);
+    covariance += DMatrix::identity(dimension, dimension) * ridge;
+    covariance.clone().try_inverse().unwrap_or_else(|| {
+        covariance
+            .pseudo_inverse(1e-12)
+            .expect("the epsilon is positive")
+    })
+}
+
+fn cos_similarity(center1: &[f64], center2: &[f64]) -> f64 {
+    assert_eq!(
+        center1.len(),
+        center2.len(),
+        "center1 and center2 should have the same length"
+    );
+    // calculate the cosine similarity between two vectors
+    let dot_product_result = dot(center1, center2);
+    let center1_norm = dot(center1, center1).sqrt();
+    let center2_norm = dot(center2, center2).sqrt();
+    dot_product_result / (center1_norm * center2_norm)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_metrics() {
+        let (a, b) = ([1.0, 0.0], [0.0, 2.0]);
+        let score = |metric: Metric| Scorer::new(metric, &[]).score(&a, &b);
+        assert_abs_diff_eq!(score(Metric::Cosine), 0.0);
+        assert_abs_diff_eq!(score(Metric::Euclidean), 5.0f64.sqrt());
+        assert_abs_diff_eq!(score(Metric::Dot), 0.0);
+        assert_abs_diff_eq!(score(Metric::Angular), 0.5);
+        assert_eq!(Scorer::new(Metric::Euclidean, &[]).affinity(2.0), -2.0);
+    }
+
+    #[test]
+    fn test_mahalanobis() {
+        // the first dimension varies ten times more than the second one
+        let tokens: Vec<Token> = [[10.0, 1.0], [-10.0, -1.0], [10.0, -1.0], [-10.0, 1.0]]
+            .iter()
+            .enumerate()
+            .map(|(i, embedding)| Token::new("w".to_string(), i, 0, embedding.to_vec()))
+            .collect();
+        let scorer = Scorer::new(Metric::Mahalanobis, &tokens);
+        let along_first = scorer.score(&[0.0, 0.0], &[10.0, 0.0]);
+        let along_second = scorer.score(&[0.0, 0.0], &[0.0, 1.0]);
+        assert_abs_diff_eq!(along_first, along_second, epsilon = 1e-4);
+    }
+}
@@ -1,7 +1,10 @@
 use crate::space::space_generator::Space;
 
 pub mod calculator;
+pub mod metric;
+pub mod profile;
 
+#[allow(dead_code)]
 pub trait SpaceCalculator {
     fn new(model_name: String, bias_free_token_space: Space, bias_group_spaces: Vec<Space>)
         -> Self;
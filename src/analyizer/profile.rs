@@ -0,0 +1,73 @@
+use crate::analyizer::calculator::Calculator;
+use pyo3::{pyclass, pymethods};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct LayerBias {
+    pub(crate) layer: usize,
+    pub(crate) bias: f64,
+    pub(crate) bias_per_group: HashMap<String, f64>,
+    pub(crate) top_biased_tokens: Vec<(String, f64)>,
+}
+
+/// The bias of one model measured layer by layer
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct LayerProfile {
+    pub(crate) model_name: String,
+    pub(crate) layers: Vec<LayerBias>,
+}
+
+impl LayerProfile {
+    pub fn new(model_name: String) -> Self {
+        LayerProfile {
+            model_name,
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, layer: usize, calculator: &Calculator, top_k: usize) {
+        self.layers.push(LayerBias {
+            layer,
+            bias: calculator.get_bias(),
+            bias_per_group: calculator.get_bias_per_group(),
+            top_biased_tokens: calculator.get_top_biased_tokens(top_k),
+        });
+    }
+}
+
+// Expose to Python
+#[pymethods]
+impl LayerProfile {
+    fn get_layers(&self) -> Vec<usize> {
+        self.layers.iter().map(|layer| layer.layer).collect()
+    }
+
+    fn get_bias(&self) -> Vec<f64> {
+        self.layers.iter().map(|layer| layer.bias).collect()
+    }
+
+    fn get_bias_per_group(&self) -> HashMap<String, Vec<f64>> {
+        let mut bias_per_group: HashMap<String, Vec<f64>> = HashMap::new();
+        for layer in &self.layers {
+            for (group_name, bias) in &layer.bias_per_group {
+                bias_per_group
+                    .entry(group_name.clone())
+                    .or_default()
+                    .push(*bias);
+            }
+        }
+        bias_per_group
+    }
+
+    fn get_top_biased_tokens(&self) -> HashMap<usize, Vec<(String, f64)>> {
+        self.layers
+            .iter()
+            .map(|layer| (layer.layer, layer.top_biased_tokens.clone()))
+            .collect()
+    }
+
+    fn get_model_name(&self) -> String {
+        self.model_name.clone()
+    }
+}
@@ -0,0 +1,200 @@
+use crate::embedding::models::{Line, Token};
+use crate::fio::reader::error::DimensionError;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyBytes;
+use pyo3::{PyAny, PyResult};
+
+/// A row major float matrix, `layers` is set for `(tokens, layers, dim)` arrays
+pub struct Matrix {
+    pub rows: usize,
+    pub layers: Option<usize>,
+    pub dimension: usize,
+    pub values: Vec<f64>,
+}
+
+impl Matrix {
+    fn row(&self, index: usize) -> &[f64] {
+        let row_len = self.layers.unwrap_or(1) * self.dimension;
+        &self.values[index * row_len..(index + 1) * row_len]
+    }
+}
+
+/// Read a NumPy array, or any nested sequence of floats, into a matrix.
+///
+/// The stable Python ABI has no buffer protocol, so NumPy arrays are copied once
+/// through `tobytes()`; no copy is made by `astype` when they already hold float64.
+pub fn extract_matrix(embeddings: &PyAny) -> PyResult<Matrix> {
+    if embeddings.hasattr("shape")? && embeddings.hasattr("tobytes")? {
+        let shape: Vec<usize> = embeddings.getattr("shape")?.extract()?;
+        let kwargs = pyo3::types::PyDict::new(embeddings.py());
+        kwargs.set_item("copy", false)?;
+        let bytes = embeddings
+            .call_method("astype", ("<f8",), Some(kwargs))?
+            .call_method0("tobytes")?;
+        let values: Vec<f64> = bytes
+            .downcast::<PyBytes>()?
+            .as_bytes()
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        return match shape.as_slice() {
+            [rows, dimension] => Ok(Matrix {
+                rows: *rows,
+                layers: None,
+                dimension: *dimension,
+                values,
+            }),
+            [rows, layers, dimension] => Ok(Matrix {
+                rows: *rows,
+                layers: Some(*layers),
+                dimension: *dimension,
+                values,
+            }),
+            _ => Err(DimensionError::new_err(format!(
+                "expected an array of shape (tokens, dim) or (tokens, layers, dim), got {:?}",
+                shape
+            ))),
+        };
+    }
+
+    let rows: Vec<Vec<f64>> = embeddings.extract()?;
+    let dimension = rows.first().map(|row| row.len()).unwrap_or(0);
+    if let Some((index, row)) = rows
+        .iter()
+        .enumerate()
+        .find(|(_, row)| row.len() != dimension)
+    {
+        return Err(DimensionError::new_err(format!(
+            "row {} has {} values, expected {}",
+            index,
+            row.len(),
+            dimension
+        )));
+    }
+    Ok(Matrix {
+        rows: rows.len(),
+        layers: None,
+        dimension,
+        values: rows.into_iter().flatten().collect(),
+    })
+}
+
+/// Build the lines from the matrix and its parallel token lists, the same way the
+/// NumPy reader does: without line numbers every token is its own line.
+/// Fails on an array without tokens or values.
+pub fn matrix_to_lines(
+    matrix: &Matrix,
+    words: Vec<String>,
+    line_nums: Option<Vec<usize>>,
+    positions: Option<Vec<usize>>,
+) -> PyResult<Vec<Line>> {
+    if matrix.rows == 0 || matrix.dimension == 0 || matrix.layers == Some(0) {
+        return Err(PyValueError::new_err(format!(
+            "the embeddings are empty: {} tokens, {} layers of dimension {}",
+            matrix.rows,
+            matrix.layers.unwrap_or(1),
+            matrix.dimension
+        )));
+    }
+    let check_len = |name: &str, len: usize| {
+        if len == matrix.rows {
+            Ok(())
+        } else {
+            Err(DimensionError::new_err(format!(
+                "the embeddings have {} rows but {} {} are given",
+                matrix.rows, len, name
+            )))
+        }
+    };
+    check_len("words", words.len())?;
+    if let Some(line_nums) = &line_nums {
+        check_len("line numbers", line_nums.len())?;
+    }
+    if let Some(positions) = &positions {
+        check_len("positions", positions.len())?;
+    }
+
+    let mut lines: Vec<Line> = Vec::new();
+    for (index, word) in words.into_iter().enumerate() {
+        let line_num = line_nums.as_ref().map(|x| x[index]).unwrap_or(index);
+        let position = positions.as_ref().map(|x| x[index]).unwrap_or(0);
+
+        if lines.last().map(|line| line.line_num) != Some(line_num) {
+            lines.push(Line {
+                tokens: Vec::new(),
+                line_num,
+            });
+        }
+        let line = lines.last_mut().unwrap();
+
+        let row = matrix.row(index);
+        match matrix.layers {
+            None => line
+                .tokens
+                .push(Token::new(word, position, line_num, row.to_vec())),
+            Some(_) => {
+                for (layer, values) in row.chunks(matrix.dimension).enumerate() {
+                    line.tokens.push(
+                        Token::new(word.clone(), position, line_num, values.to_vec())
+                            .with_layer(Some(layer)),
+                    );
+                }
+            }
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matrix_to_lines() {
+        let matrix = Matrix {
+            rows: 3,
+            layers: None,
+            dimension: 2,
+            values: vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+        };
+        let words = vec!["he".to_string(), "is".to_string(), "she".to_string()];
+        let lines =
+            matrix_to_lines(&matrix, words, Some(vec![0, 0, 1]), Some(vec![0, 1, 0])).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].tokens[1].word, "is");
+        assert_eq!(lines[0].tokens[1].position, 1);
+        assert_eq!(lines[1].tokens[0].embedding, vec![5.0, 6.0]);
+    }
+
+    #[test]
+    fn test_matrix_to_lines_with_layers() {
+        let matrix = Matrix {
+            rows: 1,
+            layers: Some(2),
+            dimension: 2,
+            values: vec![1.0, 2.0, 3.0, 4.0],
+        };
+        let lines = matrix_to_lines(&matrix, vec!["he".to_string()], None, None).unwrap();
+        assert_eq!(lines[0].tokens.len(), 2);
+        assert_eq!(lines[0].tokens[1].layer, Some(1));
+        assert_eq!(lines[0].tokens[1].embedding, vec![3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_empty_matrix_is_an_error() {
+        let matrix = Matrix {
+            rows: 0,
+            layers: None,
+            dimension: 4,
+            values: Vec::new(),
+        };
+        assert!(matrix_to_lines(&matrix, Vec::new(), None, None).is_err());
+        let matrix = Matrix {
+            rows: 1,
+            layers: None,
+            dimension: 0,
+            values: Vec::new(),
+        };
+        assert!(matrix_to_lines(&matrix, vec!["he".to_string()], None, None).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use crate::embedding::models::{Line, Token};
+use crate::fio::reader::error::ReadError;
+use pyo3::{FromPyObject, PyAny, PyErr, PyResult};
+use std::collections::BTreeSet;
+
+/// Which layer representation(s) of a token to build the spaces from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerSelection {
+    /// The highest layer available for each token.
+    Last,
+    /// One layer, by its ConceptX `index`.
+    Layer(usize),
+    /// The listed layers (all layers when `None`) concatenated in order.
+    Concat(Option<Vec<usize>>),
+    /// The element-wise mean over all layers.
+    Mean,
+}
+
+impl<'a> FromPyObject<'a> for LayerSelection {
+    fn extract(obj: &'a PyAny) -> PyResult<Self> {
+        if let Ok(layer) = obj.extract::<usize>() {
+            Ok(LayerSelection::Layer(layer))
+        } else if let Ok(layers) = obj.extract::<Vec<usize>>() {
+            Ok(LayerSelection::Concat(Some(layers)))
+        } else if let Ok(string) = obj.extract::<&str>() {
+            match string {
+                "last" => Ok(LayerSelection::Last),
+                "concat" => Ok(LayerSelection::Concat(None)),
+                "mean" => Ok(LayerSelection::Mean),
+                _ => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid layer selection: {}",
+                    string
+                ))),
+            }
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Invalid type for layer selection, expected int, list of int or str",
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for LayerSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayerSelection::Last => write!(f, "last"),
+            LayerSelection::Layer(layer) => write!(f, "{}", layer),
+            LayerSelection::Concat(None) => write!(f, "concat"),
+            LayerSelection::Concat(Some(layers)) => write!(f, "concat {:?}", layers),
+            LayerSelection::Mean => write!(f, "mean"),
+        }
+    }
+}
+
+/// All layer indices present in the data, in ascending order.
+pub fn available_layers(lines: &[Line]) -> Vec<usize> {
+    lines
+        .iter()
+        .flat_map(|line| line.tokens.iter().filter_map(|token| token.layer))
+        .collect::<BTreeSet<usize>>()
+        .into_iter()
+        .collect()
+}
+
+/// The layers of the data to profile, the `requested` ones or all of them.
+///
+/// Fails when there is no layer to profile, e.g. a file without a layer column.
+pub fn profiled_layers(
+    lines: &[Line],
+    requested: Option<&[usize]>,
+    path: &str,
+) -> PyResult<Vec<usize>> {
+    let layers: Vec<usize> = available_layers(lines)
+        .into_iter()
+        .filter(|layer| requested.is_none_or(|requested| requested.contains(layer)))
+        .collect();
+    if layers.is_empty() {
+        let requested = match requested {
+            Some(requested) => format!("{:?}", requested),
+            None => "all".to_string(),
+        };
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "{}: no layer to profile (requested layers: {}, found: {:?})",
+            path,
+            requested,
+            available_layers(lines)
+        )));
+    }
+    Ok(layers)
+}
+
+/// Reduce every line to one token per position, following `selection`.
+///
+/// The readers emit one token per (position, layer); tokens of the same
+/// position are expected to be adjacent within a line.
+/// Fails when a token lacks one of the layers asked for, e.g. ragged layers in the file.
+#[allow(dead_code)]
+pub fn select_layers(lines: &[Line], selection: &LayerSelection) -> Result<Vec<Line>, ReadError> {
+    lines
+        .iter()
+        .map(|line| select_line_layers(line, selection))
+        .collect()
+}
+
+pub fn select_line_layers(line: &Line, selection: &LayerSelection) -> Result<Line, ReadError> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut start = 0;
+    while start < line.tokens.len() {
+        let position = line.tokens[start].position;
+        let mut end = start;
+        while end < line.tokens.len() && line.tokens[end].position == position {
+            end += 1;
+        }
+        tokens.push(select_token(&line.tokens[start..end], selection)?);
+        start = end;
+    }
+    Ok(Line {
+        tokens,
+        line_num: line.line_num,
+    })
+}
+
+fn select_token(group: &[Token], selection: &LayerSelection) -> Result<Token, ReadError> {
+    // tokens without layer information (e.g. static embeddings) pass through
+    if group.len() == 1 && group[0].layer.is_none() {
+        return Ok(group[0].clone());
+    }
+
+    let error = |message: String| ReadError::Layer {
+        line: group[0].line_num,
+        word: group[0].word.clone(),
+        message,
+    };
+    let find_layer = |layer: usize| -> Result<&Token, ReadError> {
+        group
+            .iter()
+            .find(|token| token.layer == Some(layer))
+            .ok_or_else(|| error(format!("layer {} is not available", layer)))
+    };
+
+    Ok(match selection {
+        LayerSelection::Last => group
+            .iter()
+            .max_by_key(|token| token.layer)
+            .unwrap()
+            .clone(),
+        LayerSelection::Layer(layer) => find_layer(*layer)?.clone(),
+        LayerSelection::Concat(layers) => {
+            let mut sorted: Vec<&Token> = match layers {
+                Some(layers) => layers
+                    .iter()
+                    .map(|layer| find_layer(*layer))
+                    .collect::<Result<_, _>>()?,
+                None => group.iter().collect(),
+            };
+            if layers.is_none() {
+                sorted.sort_by_key(|token| token.layer);
+            }
+            let embedding = sorted
+                .iter()
+                .flat_map(|token| token.embedding.iter().cloned())
+                .collect();
+            merged(&group[0], embedding)
+        }
+        LayerSelection::Mean => {
+            let mut embedding = vec![0.0; group[0].embedding.len()];
+            for token in group {
+                if token.embedding.len() != embedding.len() {
+                    return Err(error(
+                        "all layers should have the same dimension to be averaged".to_string(),
+                    ));
+                }
+                for (sum, value) in embedding.iter_mut().zip(&token.embedding) {
+                    *sum += value;
+                }
+            }
+            embedding
+                .iter_mut()
+                .for_each(|sum| *sum /= group.len() as f64);
+            merged(&group[0], embedding)
+        }
+    })
+}
+
+fn merged(token: &Token, embedding: Vec<f64>) -> Token {
+    token.with_embedding(embedding).with_layer(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line() -> Vec<Line> {
+        let tokens = vec![
+            Token::new("he".to_string(), 0, 0, vec![1.0, 2.0]).with_layer(Some(0)),
+            Token::new("he".to_string(), 0, 0, vec![3.0, 4.0]).with_layer(Some(1)),
+            Token::new("is".to_string(), 1, 0, vec![5.0, 6.0]).with_layer(Some(0)),
+            Token::new("is".to_string(), 1, 0, vec![7.0, 8.0]).with_layer(Some(1)),
+        ];
+        vec![Line {
+            tokens,
+            line_num: 0,
+        }]
+    }
+
+    #[test]
+    fn test_available_layers() {
+        assert_eq!(available_layers(&line()), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_profiled_layers() {
+        assert_eq!(
+            profiled_layers(&line(), None, "a.json").unwrap(),
+            vec![0, 1]
+        );
+        assert_eq!(
+            profiled_layers(&line(), Some(&[1, 5]), "a.json").unwrap(),
+            vec![1]
+        );
+        assert!(profiled_layers(&line(), Some(&[5]), "a.json").is_err());
+
+        let mut without_layers = line();
+        for token in &mut without_layers[0].tokens {
+            token.layer = None;
+        }
+        assert!(profiled_layers(&without_layers, None, "a.json").is_err());
+    }
+
+    #[test]
+    fn test_select_one_layer() {
+        let lines = select_layers(&line(), &LayerSelection::Layer(0)).unwrap();
+        assert_eq!(lines[0].tokens.len(), 2);
+        assert_eq!(lines[0].tokens[1].embedding, vec![5.0, 6.0]);
+        assert_eq!(lines[0].tokens[1].layer, Some(0));
+
+        let lines = select_layers(&line(), &LayerSelection::Last).unwrap();
+        assert_eq!(lines[0].tokens[0].embedding, vec![3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_select_concat_and_mean() {
+        let lines = select_layers(&line(), &LayerSelection::Concat(Some(vec![1, 0]))).unwrap();
+        assert_eq!(lines[0].tokens[0].embedding, vec![3.0, 4.0, 1.0, 2.0]);
+        assert_eq!(lines[0].tokens[0].layer, None);
+
+        let lines = select_layers(&line(), &LayerSelection::Mean).unwrap();
+        assert_eq!(lines[0].tokens[1].embedding, vec![6.0, 7.0]);
+    }
+
+    #[test]
+    fn test_missing_layer_is_an_error() {
+        // "is" only has its first layer
+        let mut lines = line();
+        lines[0].tokens.pop();
+        let error = select_layers(&lines, &LayerSelection::Layer(1)).unwrap_err();
+        assert!(matches!(&error, ReadError::Layer { word, .. } if word == "is"));
+        assert!(select_layers(&lines, &LayerSelection::Layer(5)).is_err());
+        assert!(select_layers(&lines, &LayerSelection::Layer(0)).is_ok());
+    }
+}
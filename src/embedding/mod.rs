@@ -1 +1,5 @@
+pub mod array;
+pub mod layer;
 pub mod models;
+pub mod normalizer;
+pub mod pooling;
@@ -11,18 +11,57 @@ pub struct Token {
     pub line_num: usize,
     pub embedding: Vec<f64>,
     pub token_id: String,
+    /// The model layer the embedding was taken from, `None` when the embedding
+    /// is not tied to a single layer (e.g. layers were concatenated or averaged).
+    pub layer: Option<usize>,
+    /// The token as written by the tokenizer, before normalization
+    pub raw_word: String,
+    /// Whether the token continues the previous word, e.g. `##se` in `nur ##se`
+    pub continuation: bool,
+    /// The index of the file the token was read from, when several files are read
+    pub source_id: usize,
 }
 
 impl Token {
     pub fn new(word: String, position: usize, line_num: usize, embedding: Vec<f64>) -> Self {
         Token {
             word: word.clone(),
-            position: position,
-            line_num: line_num,
-            embedding: embedding,
-            token_id: format!("{}:{}:{}", word, position, line_num),
+            position,
+            line_num,
+            embedding,
+            token_id: token_id(&word, position, line_num, 0),
+            layer: None,
+            raw_word: word,
+            continuation: false,
+            source_id: 0,
         }
     }
+
+    /// The same token with another embedding, e.g. after a projection
+    pub fn with_embedding(&self, embedding: Vec<f64>) -> Self {
+        Token {
+            word: self.word.clone(),
+            token_id: self.token_id.clone(),
+            raw_word: self.raw_word.clone(),
+            embedding,
+            ..*self
+        }
+    }
+
+    pub fn with_layer(mut self, layer: Option<usize>) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    /// Set the id again after the word, position or source changed
+    pub fn update_id(&mut self) {
+        self.token_id = token_id(&self.word, self.position, self.line_num, self.source_id);
+    }
+}
+
+/// `word:position:line_num:source_id`, unique across the files read together
+fn token_id(word: &str, position: usize, line_num: usize, source_id: usize) -> String {
+    format!("{}:{}:{}:{}", word, position, line_num, source_id)
 }
 
 pub trait TokenOperators {
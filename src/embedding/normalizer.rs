@@ -0,0 +1,220 @@
+use crate::embedding::models::Line;
+use pyo3::{pyclass, FromPyObject, PyAny, PyErr, PyResult};
+use unicode_normalization::UnicodeNormalization;
+
+/// How a tokenizer marks subword pieces
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenizerPreset {
+    /// Strip every known marker anywhere in the token, the historical behaviour
+    Legacy,
+    /// BERT style, `##` starts a continuation piece
+    WordPiece,
+    /// GPT-2 style, `Ġ` starts a new word
+    Bpe,
+    /// T5/XLM-R style, `▁` starts a new word
+    SentencePiece,
+    /// Keep the tokens as they are
+    Plain,
+}
+
+impl<'a> FromPyObject<'a> for TokenizerPreset {
+    fn extract(obj: &'a PyAny) -> PyResult<Self> {
+        if let Ok(string) = obj.extract::<&str>() {
+            match string {
+                "legacy" => Ok(TokenizerPreset::Legacy),
+                "wordpiece" => Ok(TokenizerPreset::WordPiece),
+                "bpe" => Ok(TokenizerPreset::Bpe),
+                "sentencepiece" => Ok(TokenizerPreset::SentencePiece),
+                "none" => Ok(TokenizerPreset::Plain),
+                _ => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid tokenizer preset: {}",
+                    string
+                ))),
+            }
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Invalid type for tokenizer preset, expected str",
+            ))
+        }
+    }
+}
+
+// the ConceptX dumps sometimes keep the markers as escaped literals
+const BPE_MARKERS: [&str; 2] = ["Ġ", "\\u0120"];
+const SENTENCEPIECE_MARKERS: [&str; 2] = ["▁", "\\u2581"];
+const WORDPIECE_MARKER: &str = "##";
+
+/// Turns the raw tokenizer output into the words matched against the seeds
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    pub(crate) preset: TokenizerPreset,
+    /// Literal `(pattern, replacement)` pairs applied after the preset
+    pub(crate) rules: Vec<(String, String)>,
+    pub(crate) lowercase: bool,
+    pub(crate) nfkc: bool,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Normalizer::new(TokenizerPreset::Legacy, Vec::new(), false, false)
+    }
+}
+
+impl Normalizer {
+    pub fn new(
+        preset: TokenizerPreset,
+        rules: Vec<(String, String)>,
+        lowercase: bool,
+        nfkc: bool,
+    ) -> Self {
+        Normalizer {
+            preset,
+            rules,
+            lowercase,
+            nfkc,
+        }
+    }
+
+    /// Normalize every token of the line, keeping the raw form in `raw_word`
+    pub fn normalize_line(&self, line: &mut Line) {
+        // tokens of the same position (one per layer) share the previous piece
+        let mut previous: Option<(usize, String)> = None;
+        let mut current: Option<(usize, String)> = None;
+        for token in &mut line.tokens {
+            if current.as_ref().map(|(position, _)| *position) != Some(token.position) {
+                previous = current.take();
+                current = Some((token.position, token.raw_word.clone()));
+            }
+            let previous_raw = previous.as_ref().map(|(_, raw)| raw.as_str());
+            let (word, continuation) = self.normalize(&token.raw_word, previous_raw);
+            token.word = word;
+            token.continuation = continuation;
+            token.update_id();
+        }
+    }
+
+    /// The normalized word and whether it continues the previous piece
+    pub fn normalize(&self, raw: &str, previous_raw: Option<&str>) -> (String, bool) {
+        let (word, continuation) = self.strip_markers(raw, previous_raw);
+        let mut word = self
+            .rules
+            .iter()
+            .fold(word, |word, (pattern, replacement)| {
+                word.replace(pattern, replacement)
+            });
+        if self.nfkc {
+            word = word.nfkc().collect();
+        }
+        if self.lowercase {
+            word = word.to_lowercase();
+        }
+        (word, continuation)
+    }
+
+    fn strip_markers(&self, raw: &str, previous_raw: Option<&str>) -> (String, bool) {
+        // a piece without a word start marker continues the previous one, unless it
+        // starts the line or follows a special token such as `<s>`
+        let continues_previous =
+            || previous_raw.is_some_and(|previous| !is_special(previous)) && !is_special(raw);
+
+        match self.preset {
+            TokenizerPreset::Legacy => {
+                let word = [WORDPIECE_MARKER]
+                    .iter()
+                    .chain(BPE_MARKERS.iter())
+                    .chain(SENTENCEPIECE_MARKERS.iter())
+                    .fold(raw.to_string(), |word, marker| word.replace(marker, ""));
+                (word, raw.starts_with(WORDPIECE_MARKER))
+            }
+            TokenizerPreset::WordPiece => match raw.strip_prefix(WORDPIECE_MARKER) {
+                Some(word) => (word.to_string(), true),
+                None => (raw.to_string(), false),
+            },
+            TokenizerPreset::Bpe => match strip_any_prefix(raw, &BPE_MARKERS) {
+                Some(word) => (word.to_string(), false),
+                None => (raw.to_string(), continues_previous()),
+            },
+            TokenizerPreset::SentencePiece => match strip_any_prefix(raw, &SENTENCEPIECE_MARKERS) {
+                Some(word) => (word.to_string(), false),
+                None => (raw.to_string(), continues_previous()),
+            },
+            TokenizerPreset::Plain => (raw.to_string(), false),
+        }
+    }
+}
+
+fn strip_any_prefix<'a>(raw: &'a str, markers: &[&str]) -> Option<&'a str> {
+    markers.iter().find_map(|marker| raw.strip_prefix(marker))
+}
+
+/// Special tokens like `[CLS]`, `<s>` or `</s>`
+fn is_special(raw: &str) -> bool {
+    raw.len() > 2
+        && ((raw.starts_with('[') && raw.ends_with(']'))
+            || (raw.starts_with('<') && raw.ends_with('>')))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::embedding::models::Token;
+
+    fn line(words: &[&str]) -> Line {
+        Line {
+            tokens: words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| Token::new(word.to_string(), i, 0, vec![0.0]))
+                .collect(),
+            line_num: 0,
+        }
+    }
+
+    fn normalized(normalizer: &Normalizer, words: &[&str]) -> Vec<(String, bool)> {
+        let mut line = line(words);
+        normalizer.normalize_line(&mut line);
+        line.tokens
+            .into_iter()
+            .map(|token| (token.word, token.continuation))
+            .collect()
+    }
+
+    #[test]
+    fn test_legacy() {
+        let normalizer = Normalizer::default();
+        let words = normalized(&normalizer, &["nur", "##se", "Ġhe", "▁she"]);
+        assert_eq!(
+            words,
+            vec![
+                ("nur".to_string(), false),
+                ("se".to_string(), true),
+                ("he".to_string(), false),
+                ("she".to_string(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bpe() {
+        let normalizer = Normalizer::new(TokenizerPreset::Bpe, Vec::new(), false, false);
+        let words = normalized(&normalizer, &["<s>", "The", "Ġnur", "se"]);
+        assert_eq!(words[1], ("The".to_string(), false));
+        assert_eq!(words[2], ("nur".to_string(), false));
+        assert_eq!(words[3], ("se".to_string(), true));
+    }
+
+    #[test]
+    fn test_rules_nfkc_and_lowercase() {
+        let normalizer = Normalizer::new(
+            TokenizerPreset::WordPiece,
+            vec![("’".to_string(), "'".to_string())],
+            true,
+            true,
+        );
+        let mut line = line(&["Ｈe’s"]);
+        normalizer.normalize_line(&mut line);
+        assert_eq!(line.tokens[0].word, "he's");
+        assert_eq!(line.tokens[0].raw_word, "Ｈe’s");
+    }
+}
@@ -0,0 +1,155 @@
+use crate::embedding::models::{Line, Token};
+use pyo3::{FromPyObject, PyAny, PyErr, PyResult};
+
+/// How the embeddings of the pieces of one word are combined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pooling {
+    Mean,
+    First,
+    Last,
+    Max,
+}
+
+impl<'a> FromPyObject<'a> for Pooling {
+    fn extract(obj: &'a PyAny) -> PyResult<Self> {
+        if let Ok(string) = obj.extract::<&str>() {
+            match string {
+                "mean" => Ok(Pooling::Mean),
+                "first" => Ok(Pooling::First),
+                "last" => Ok(Pooling::Last),
+                "max" => Ok(Pooling::Max),
+                _ => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid pooling: {}",
+                    string
+                ))),
+            }
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Invalid type for pooling, expected str",
+            ))
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn pool_subwords(lines: &[Line], pooling: Pooling) -> Vec<Line> {
+    lines.iter().map(|line| pool_line(line, pooling)).collect()
+}
+
+/// Merge every continuation piece into the word it continues.
+///
+/// Expects one token per position, i.e. layers already selected. The positions of the
+/// pooled tokens are word indices within the line.
+pub fn pool_line(line: &Line, pooling: Pooling) -> Line {
+    let mut words: Vec<Vec<&Token>> = Vec::new();
+    for token in &line.tokens {
+        match words.last_mut() {
+            Some(pieces) if token.continuation => pieces.push(token),
+            _ => words.push(vec![token]),
+        }
+    }
+
+    let tokens = words
+        .into_iter()
+        .enumerate()
+        .map(|(position, pieces)| pool_word(&pieces, position, pooling))
+        .collect();
+
+    Line {
+        tokens,
+        line_num: line.line_num,
+    }
+}
+
+fn pool_word(pieces: &[&Token], position: usize, pooling: Pooling) -> Token {
+    let embedding = pool_embeddings(pieces, pooling);
+
+    let word: String = pieces.iter().map(|piece| piece.word.as_str()).collect();
+    let mut token = pieces[0].with_embedding(embedding);
+    token.word = word;
+    token.raw_word = pieces
+        .iter()
+        .map(|piece| piece.raw_word.as_str())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    token.position = position;
+    token.update_id();
+    token
+}
+
+/// Combine the embeddings of several tokens into one
+pub fn pool_embeddings(pieces: &[&Token], pooling: Pooling) -> Vec<f64> {
+    match pooling {
+        Pooling::First => pieces[0].embedding.clone(),
+        Pooling::Last => pieces[pieces.len() - 1].embedding.clone(),
+        Pooling::Mean | Pooling::Max => {
+            let mut embedding = pieces[0].embedding.clone();
+            for piece in &pieces[1..] {
+                assert_eq!(
+                    piece.embedding.len(),
+                    embedding.len(),
+                    "All pieces of a word should have the same dimension"
+                );
+                for (pooled, value) in embedding.iter_mut().zip(&piece.embedding) {
+                    match pooling {
+                        Pooling::Max => *pooled = pooled.max(*value),
+                        _ => *pooled += value,
+                    }
+                }
+            }
+            if pooling == Pooling::Mean {
+                embedding
+                    .iter_mut()
+                    .for_each(|pooled| *pooled /= pieces.len() as f64);
+            }
+            embedding
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line() -> Line {
+        let pieces = [("the", false), ("nur", false), ("se", true), ("s", true)];
+        Line {
+            tokens: pieces
+                .iter()
+                .enumerate()
+                .map(|(i, (word, continuation))| {
+                    let mut token = Token::new(word.to_string(), i, 0, vec![i as f64, -(i as f64)]);
+                    token.continuation = *continuation;
+                    token
+                })
+                .collect(),
+            line_num: 0,
+        }
+    }
+
+    #[test]
+    fn test_pool_words() {
+        let line = pool_line(&line(), Pooling::Mean);
+        assert_eq!(line.tokens.len(), 2);
+        assert_eq!(line.tokens[1].word, "nurses");
+        assert_eq!(line.tokens[1].raw_word, "nur se s");
+        assert_eq!(line.tokens[1].position, 1);
+        assert_eq!(line.tokens[1].embedding, vec![2.0, -2.0]);
+    }
+
+    #[test]
+    fn test_pooling_strategies() {
+        assert_eq!(
+            pool_line(&line(), Pooling::First).tokens[1].embedding,
+            vec![1.0, -1.0]
+        );
+        assert_eq!(
+            pool_line(&line(), Pooling::Last).tokens[1].embedding,
+            vec![3.0, -3.0]
+        );
+        assert_eq!(
+            pool_line(&line(), Pooling::Max).tokens[1].embedding,
+            vec![3.0, -1.0]
+        );
+    }
+}
@@ -0,0 +1,493 @@
+use crate::embedding::models::{Line, Token};
+use crate::fio::reader::error::ReadError;
+use crate::fio::reader::numpy::vocab_path;
+use crate::fio::reader::{LineStream, ReadOptions, ReaderType};
+use crate::util::constant;
+use crate::util::progress_bar::ProgressBar;
+use crate::util::Message;
+use std::io::{Read, Seek, Write};
+use xxhash_rust::xxh3::Xxh3;
+
+/// The cache of a source file is `<path>.wfcache`, next to it:
+///
+/// - a 64 byte header: magic, version, content hash of the source, dimension, number of
+///   lines and tokens, whether bad lines were skipped, and the size and modification time
+///   stamp of the source
+/// - the embeddings of all tokens as one contiguous little endian f64 matrix
+/// - the token table: per line its number and token count, then per token its position,
+///   layer and raw word
+///
+/// The tokens are cached as the reader returns them, before normalization, so the same
+/// cache serves every normalizer.
+const MAGIC: &[u8; 8] = b"WFCACHE\0";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 64;
+const NO_LAYER: u64 = u64::MAX;
+
+pub fn cache_path(path: &str) -> String {
+    format!("{}{}", path, constant::CACHE_EXTENSION)
+}
+
+/// The raw lines of the source, read from its cache when the cache matches the content
+/// of the source, otherwise read by `read_source` and written to the cache on the way.
+///
+/// The source is only hashed when its size or modification time changed since the cache
+/// was written.
+pub fn cached_lines(
+    path: &str,
+    reader_type: ReaderType,
+    options: &ReadOptions,
+    read_source: impl FnOnce() -> Result<LineStream, ReadError>,
+) -> Result<LineStream, ReadError> {
+    let stamp = stamp(path, reader_type, options)?;
+    let cache_path = cache_path(path);
+    let mut hash = None;
+    if let Some(lines) = CacheLines::open(&cache_path, options) {
+        let fresh = match stamp {
+            Some(stamp) if stamp == lines.header.stamp => true,
+            _ => {
+                let content = content_hash(path, reader_type, options)?;
+                hash = Some(content);
+                content == lines.header.hash
+            }
+        };
+        if fresh {
+            Message::cache_loaded(&cache_path);
+            return Ok(Box::new(lines));
+        }
+    }
+
+    let hash = match hash {
+        Some(hash) => hash,
+        None => content_hash(path, reader_type, options)?,
+    };
+    let lines = read_source()?;
+    let writer = match CacheWriter::create(
+        &cache_path,
+        hash,
+        stamp.unwrap_or(0),
+        options.skip_bad_lines,
+    ) {
+        Ok(writer) => Some(writer),
+        Err(error) => {
+            Message::cache_not_written(&cache_path, &error);
+            None
+        }
+    };
+    Ok(Box::new(CachingLines { lines, writer }))
+}
+
+/// The size and modification time of the source and of its sidecar for NumPy arrays,
+/// `None` when the file system does not keep modification times
+fn stamp(
+    path: &str,
+    reader_type: ReaderType,
+    options: &ReadOptions,
+) -> Result<Option<u64>, ReadError> {
+    let mut hasher = Xxh3::new();
+    hasher.update(&[reader_type as u8]);
+    let mut paths = vec![path.to_string()];
+    if reader_type == ReaderType::Numpy {
+        paths.push(vocab_path(path, options));
+    }
+    for path in &paths {
+        let metadata = std::fs::metadata(path).map_err(|error| ReadError::io(path, error))?;
+        let modified = match metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        {
+            Some(modified) => modified,
+            None => return Ok(None),
+        };
+        hasher.update(&metadata.len().to_le_bytes());
+        hasher.update(&modified.as_nanos().to_le_bytes());
+    }
+    Ok(Some(hasher.digest()))
+}
+
+/// The source, its sidecar for NumPy arrays, and the reader used
+fn content_hash(
+    path: &str,
+    reader_type: ReaderType,
+    options: &ReadOptions,
+) -> Result<u64, ReadError> {
+    let mut hasher = Xxh3::new();
+    hasher.update(&[reader_type as u8]);
+    hash_file(&mut hasher, path)?;
+    if reader_type == ReaderType::Numpy {
+        hash_file(&mut hasher, &vocab_path(path, options))?;
+    }
+    Ok(hasher.digest())
+}
+
+fn hash_file(hasher: &mut Xxh3, path: &str) -> Result<(), ReadError> {
+    let mut file = std::fs::File::open(path).map_err(|error| ReadError::io(path, error))?;
+    let mut buffer = vec![0u8; 1 << 20];
+    loop {
+        let bytes = file
+            .read(&mut buffer)
+            .map_err(|error| ReadError::io(path, error))?;
+        if bytes == 0 {
+            return Ok(());
+        }
+        hasher.update(&buffer[..bytes]);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CacheHeader {
+    hash: u64,
+    dimension: usize,
+    num_lines: usize,
+    num_tokens: usize,
+    /// A cache built while skipping bad lines must not hide them from a strict read
+    skip_bad_lines: bool,
+    /// Matches the `stamp` of an unchanged source, which is then not hashed again
+    stamp: u64,
+}
+
+impl CacheHeader {
+    fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[..8].copy_from_slice(MAGIC);
+        bytes[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.hash.to_le_bytes());
+        bytes[24..32].copy_from_slice(&(self.dimension as u64).to_le_bytes());
+        bytes[32..40].copy_from_slice(&(self.num_lines as u64).to_le_bytes());
+        bytes[40..48].copy_from_slice(&(self.num_tokens as u64).to_le_bytes());
+        bytes[48] = self.skip_bad_lines as u8;
+        bytes[56..64].copy_from_slice(&self.stamp.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<CacheHeader> {
+        if bytes.len() < HEADER_LEN
+            || &bytes[..8] != MAGIC
+            || u32::from_le_bytes(bytes[8..12].try_into().ok()?) != VERSION
+        {
+            return None;
+        }
+        let u64_at = |start: usize| u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap());
+        Some(CacheHeader {
+            hash: u64_at(16),
+            dimension: u64_at(24) as usize,
+            num_lines: u64_at(32) as usize,
+            num_tokens: u64_at(40) as usize,
+            skip_bad_lines: bytes[48] != 0,
+            stamp: u64_at(56),
+        })
+    }
+
+    fn table_offset(&self) -> Option<usize> {
+        self.num_tokens
+            .checked_mul(self.dimension)?
+            .checked_mul(8)?
+            .checked_add(HEADER_LEN)
+    }
+}
+
+/// Yields the lines of a memory mapped cache
+struct CacheLines {
+    path: String,
+    mmap: memmap2::Mmap,
+    header: CacheHeader,
+    line_index: usize,
+    token_index: usize,
+    cursor: usize,
+    pb_readfile: ProgressBar,
+}
+
+impl CacheLines {
+    /// `None` when there is no usable cache, it is then rebuilt. Whether the cache is
+    /// still up to date with its source is left to the caller.
+    fn open(path: &str, options: &ReadOptions) -> Option<CacheLines> {
+        let file = std::fs::File::open(path).ok()?;
+        // SAFETY: the cache is only replaced by a rename, never written in place
+        let mmap = unsafe { memmap2::Mmap::map(&file) }.ok()?;
+        let header = CacheHeader::decode(&mmap)?;
+        let table_offset = header.table_offset()?;
+        if (header.skip_bad_lines && !options.skip_bad_lines) || mmap.len() < table_offset {
+            return None;
+        }
+        let pb_readfile = ProgressBar::new(
+            mmap.len() as u64,
+            constant::FILE_READING,
+            options.user_friendly,
+        );
+        Some(CacheLines {
+            path: path.to_string(),
+            cursor: table_offset,
+            mmap,
+            header,
+            line_index: 0,
+            token_index: 0,
+            pb_readfile,
+        })
+    }
+
+    fn read_u64(&mut self) -> Result<u64, ReadError> {
+        let bytes = self
+            .mmap
+            .get(self.cursor..self.cursor + 8)
+            .ok_or_else(|| self.truncated())?;
+        self.cursor += 8;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_line(&mut self) -> Result<Line, ReadError> {
+        let start = self.cursor;
+        let line_num = self.read_u64()? as usize;
+        let num_tokens = self.read_u64()? as usize;
+        // the count is only trusted as far as the header allows
+        let mut tokens =
+            Vec::with_capacity(num_tokens.min(self.header.num_tokens - self.token_index));
+        for _ in 0..num_tokens {
+            let position = self.read_u64()? as usize;
+            let layer = match self.read_u64()? {
+                NO_LAYER => None,
+                layer => Some(layer as usize),
+            };
+            let word_len = self.read_u64()? as usize;
+            let word = self
+                .mmap
+                .get(self.cursor..self.cursor.saturating_add(word_len))
+                .ok_or_else(|| self.truncated())?;
+            let word = String::from_utf8_lossy(word).to_string();
+            self.cursor += word_len;
+
+            if self.token_index == self.header.num_tokens {
+                return Err(self.truncated());
+            }
+            let row_len = self.header.dimension * 8;
+            let row_start = HEADER_LEN + self.token_index * row_len;
+            let embedding = self
+                .mmap
+                .get(row_start..row_start + row_len)
+                .ok_or_else(|| self.truncated())?
+                .chunks_exact(8)
+                .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+                .collect();
+            self.token_index += 1;
+
+            tokens.push(Token::new(word, position, line_num, embedding).with_layer(layer));
+        }
+        self.pb_readfile
+            .inc((self.cursor - start + num_tokens * self.header.dimension * 8) as u64);
+        Ok(Line { tokens, line_num })
+    }
+
+    fn truncated(&self) -> ReadError {
+        ReadError::Parse {
+            path: self.path.clone(),
+            line: self.line_index + 1,
+            offset: self.cursor as u64,
+            message: "the cache is truncated, delete it to rebuild it".to_string(),
+        }
+    }
+}
+
+impl Iterator for CacheLines {
+    type Item = Result<Line, ReadError>;
+
+    fn next(&mut self) -> Option<Result<Line, ReadError>> {
+        if self.line_index == self.header.num_lines {
+            self.pb_readfile.finish();
+            return None;
+        }
+        let line = self.read_line();
+        self.line_index = match line {
+            Ok(_) => self.line_index + 1,
+            // nothing after can be read
+            Err(_) => self.header.num_lines,
+        };
+        Some(line)
+    }
+}
+
+/// Writes the cache to a temporary file, renamed once all lines are written
+struct CacheWriter {
+    path: String,
+    tmp_path: String,
+    file: std::io::BufWriter<std::fs::File>,
+    header: CacheHeader,
+    dimension: Option<usize>,
+    table: Vec<u8>,
+    finished: bool,
+}
+
+impl CacheWriter {
+    fn create(
+        path: &str,
+        hash: u64,
+        stamp: u64,
+        skip_bad_lines: bool,
+    ) -> std::io::Result<CacheWriter> {
+        let tmp_path = format!("{}.tmp", path);
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+        file.write_all(&[0u8; HEADER_LEN])?;
+        Ok(CacheWriter {
+            path: path.to_string(),
+            tmp_path,
+            file,
+            header: CacheHeader {
+                hash,
+                dimension: 0,
+                num_lines: 0,
+                num_tokens: 0,
+                skip_bad_lines,
+                stamp,
+            },
+            dimension: None,
+            table: Vec::new(),
+            finished: false,
+        })
+    }
+
+    fn push(&mut self, line: &Line) -> std::io::Result<()> {
+        self.table
+            .extend_from_slice(&(line.line_num as u64).to_le_bytes());
+        self.table
+            .extend_from_slice(&(line.tokens.len() as u64).to_le_bytes());
+        for token in &line.tokens {
+            if *self.dimension.get_or_insert(token.embedding.len()) != token.embedding.len() {
+                return Err(std::io::Error::other(
+                    "the tokens do not all have the same dimension",
+                ));
+            }
+            for value in &token.embedding {
+                self.file.write_all(&value.to_le_bytes())?;
+            }
+            let layer = token.layer.map(|layer| layer as u64).unwrap_or(NO_LAYER);
+            self.table
+                .extend_from_slice(&(token.position as u64).to_le_bytes());
+            self.table.extend_from_slice(&layer.to_le_bytes());
+            self.table
+                .extend_from_slice(&(token.raw_word.len() as u64).to_le_bytes());
+            self.table.extend_from_slice(token.raw_word.as_bytes());
+        }
+        self.header.num_lines += 1;
+        self.header.num_tokens += line.tokens.len();
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<String> {
+        self.header.dimension = self.dimension.unwrap_or(0);
+        self.file.write_all(&self.table)?;
+        self.file.seek(std::io::SeekFrom::Start(0))?;
+        self.file.write_all(&self.header.encode())?;
+        self.file.flush()?;
+        std::fs::rename(&self.tmp_path, &self.path)?;
+        self.finished = true;
+        Ok(self.path.clone())
+    }
+}
+
+impl Drop for CacheWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+/// Passes the lines of the source through, writing them to the cache. The cache is
+/// only kept when the whole source was read without error.
+struct CachingLines {
+    lines: LineStream,
+    writer: Option<CacheWriter>,
+}
+
+impl Iterator for CachingLines {
+    type Item = Result<Line, ReadError>;
+
+    fn next(&mut self) -> Option<Result<Line, ReadError>> {
+        let line = self.lines.next();
+        match &line {
+            Some(Ok(line)) => {
+                if let Some(writer) = &mut self.writer {
+                    if let Err(error) = writer.push(line) {
+                        Message::cache_not_written(&writer.path, &error);
+                        self.writer = None;
+                    }
+                }
+            }
+            Some(Err(_)) => self.writer = None,
+            None => {
+                if let Some(writer) = self.writer.take() {
+                    let path = writer.path.clone();
+                    match writer.finish() {
+                        Ok(path) => Message::cache_written(&path),
+                        Err(error) => Message::cache_not_written(&path, &error),
+                    }
+                }
+            }
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fio::reader::read_lines;
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("wafflecone-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("conceptx.json").to_string_lossy().to_string();
+        std::fs::copy("./test_data/conceptx_layers.json", &path).unwrap();
+        let options = ReadOptions {
+            cache: true,
+            ..ReadOptions::default()
+        };
+
+        let read = read_lines(&path, ReaderType::ConceptX, &options).unwrap();
+        assert!(std::path::Path::new(&cache_path(&path)).exists());
+        let cached = read_lines(&path, ReaderType::ConceptX, &options).unwrap();
+        assert_eq!(cached.len(), read.len());
+        for (cached, read) in cached.iter().zip(&read) {
+            assert_eq!(cached.line_num, read.line_num);
+            for (cached, read) in cached.tokens.iter().zip(&read.tokens) {
+                assert_eq!(cached.token_id, read.token_id);
+                assert_eq!(cached.raw_word, read.raw_word);
+                assert_eq!(cached.layer, read.layer);
+                assert_eq!(cached.embedding, read.embedding);
+            }
+        }
+
+        // a changed source is read again
+        std::fs::copy("./test_data/conceptx.json", &path).unwrap();
+        let lines = read_lines(&path, ReaderType::ConceptX, &options).unwrap();
+        assert_eq!(lines.len(), 10);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_cache_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("wafflecone-corrupt-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("conceptx.json").to_string_lossy().to_string();
+        std::fs::copy("./test_data/conceptx.json", &path).unwrap();
+        let options = ReadOptions {
+            cache: true,
+            ..ReadOptions::default()
+        };
+        read_lines(&path, ReaderType::ConceptX, &options).unwrap();
+
+        // the first line claims more tokens than the cache holds
+        let mut bytes = std::fs::read(cache_path(&path)).unwrap();
+        let header = CacheHeader::decode(&bytes).unwrap();
+        let count = header.table_offset().unwrap() + 8;
+        bytes[count..count + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(cache_path(&path), &bytes).unwrap();
+
+        let mut lines = CacheLines::open(&cache_path(&path), &options).unwrap();
+        assert!(matches!(lines.next(), Some(Err(ReadError::Parse { .. }))));
+        assert!(lines.next().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
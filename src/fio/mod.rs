@@ -1,2 +1,4 @@
+pub mod cache;
 pub mod reader;
+pub mod seeds;
 pub mod writer;
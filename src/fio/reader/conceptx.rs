@@ -1,9 +1,14 @@
-use super::Reader;
+use super::error::ReadError;
+use super::input;
+use super::{ReadOptions, Reader};
 use crate::embedding::models::Line;
 use crate::embedding::models::Token;
 use crate::util::constant;
 use crate::util::progress_bar::ProgressBar;
+use crate::util::Message;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::io::BufRead;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -28,71 +33,188 @@ pub struct TokenConecptX {
 pub struct ConceptXReader {}
 
 impl Reader for ConceptXReader {
+    type Lines = ConceptXLines;
+
     fn new() -> Self {
         ConceptXReader {}
     }
 
-    fn read(&self, path: &str, user_friendly: bool) -> Vec<Line> {
-        let file = std::fs::File::open(path).unwrap();
-        let buf = std::io::BufReader::new(&file);
-
-        let mut pb_readfile = ProgressBar::new(
-            file.metadata().unwrap().len(),
-            constant::FILE_READING,
-            user_friendly,
-        );
-
-        let activations = buf
-            .lines()
-            .map(|line| {
-                pb_readfile.inc(line.as_ref().unwrap().len() as u64);
-                line.unwrap()
-            })
-            .map(|line| {
-                let mut activation = serde_json::from_str::<LineConceptX>(&line).unwrap();
-                activation.features.iter_mut().for_each(|x| {
-                    x.token = x.token.replace("##", "");
-                    x.token = x.token.replace("Ġ", "");
-                    x.token = x.token.replace("\\u0120", "");
-                    x.token = x.token.replace("▁", "");
-                    x.token = x.token.replace("\\u2581", "");
+    fn stream(&self, path: &str, options: &ReadOptions) -> Result<ConceptXLines, ReadError> {
+        let (reader, pb_readfile) = input::open(path, options.user_friendly)?;
+        let pool = match options.threads {
+            Some(threads) => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .map_err(|error| ReadError::io(path, std::io::Error::other(error)))?,
+            ),
+            None => None,
+        };
+
+        Ok(ConceptXLines {
+            path: path.to_string(),
+            reader,
+            skip_bad_lines: options.skip_bad_lines,
+            line_number: 0,
+            offset: 0,
+            dimension: None,
+            skipped: Vec::new(),
+            pb_readfile,
+            pool,
+            parsed: VecDeque::new(),
+            eof: false,
+        })
+    }
+}
+
+/// A line of the file waiting to be parsed
+struct RawLine {
+    text: String,
+    line_number: usize,
+    offset: u64,
+}
+
+/// Parses the ConceptX file lazily. The lines are read in batches that are parsed in
+/// parallel, then yielded in the order of the file.
+pub struct ConceptXLines {
+    path: String,
+    reader: Box<dyn BufRead>,
+    skip_bad_lines: bool,
+    line_number: usize,
+    offset: u64,
+    dimension: Option<usize>,
+    skipped: Vec<ReadError>,
+    pb_readfile: ProgressBar,
+    /// The global rayon pool is used when no thread count is given
+    pool: Option<rayon::ThreadPool>,
+    parsed: VecDeque<Result<Line, ReadError>>,
+    eof: bool,
+}
+
+impl ConceptXLines {
+    fn read_batch(&mut self) -> Result<Vec<RawLine>, ReadError> {
+        let mut batch = Vec::with_capacity(constant::PARSE_BATCH_LINES);
+        while batch.len() < constant::PARSE_BATCH_LINES {
+            let mut text = String::new();
+            let bytes = self
+                .reader
+                .read_line(&mut text)
+                .map_err(|error| ReadError::io(&self.path, error))?;
+            if bytes == 0 {
+                self.eof = true;
+                break;
+            }
+            self.line_number += 1;
+            if !text.trim().is_empty() {
+                batch.push(RawLine {
+                    text,
+                    line_number: self.line_number,
+                    offset: self.offset,
+                });
+            }
+            self.offset += bytes as u64;
+        }
+        Ok(batch)
+    }
+
+    fn parse_batch(&mut self, batch: Vec<RawLine>) {
+        let path = self.path.as_str();
+        let parse_all = || -> Vec<Result<Line, ReadError>> {
+            batch.par_iter().map(|raw| parse(path, raw)).collect()
+        };
+        let parsed = match &self.pool {
+            Some(pool) => pool.install(parse_all),
+            None => parse_all(),
+        };
+
+        // the dimension is the one of the first line, so it is checked in order
+        for (raw, line) in batch.iter().zip(parsed) {
+            let line = line.and_then(|line| self.check_dimension(line, raw.line_number));
+            self.parsed.push_back(line);
+        }
+    }
+
+    fn check_dimension(&mut self, line: Line, line_number: usize) -> Result<Line, ReadError> {
+        for token in &line.tokens {
+            let expected = *self.dimension.get_or_insert(token.embedding.len());
+            if token.embedding.len() != expected {
+                return Err(ReadError::Dimension {
+                    path: self.path.clone(),
+                    line: line_number,
+                    expected,
+                    found: token.embedding.len(),
                 });
-                activation
-            })
-            .collect::<Vec<LineConceptX>>();
+            }
+        }
+        Ok(line)
+    }
+}
+
+fn parse(path: &str, raw: &RawLine) -> Result<Line, ReadError> {
+    let activation =
+        serde_json::from_str::<LineConceptX>(&raw.text).map_err(|error| ReadError::Parse {
+            path: path.to_string(),
+            line: raw.line_number,
+            offset: raw.offset + error.column().saturating_sub(1) as u64,
+            message: error.to_string(),
+        })?;
+    Ok(converter(activation))
+}
 
-        pb_readfile.finish();
+impl Iterator for ConceptXLines {
+    type Item = Result<Line, ReadError>;
 
-        converter(activations)
+    fn next(&mut self) -> Option<Result<Line, ReadError>> {
+        loop {
+            match self.parsed.pop_front() {
+                Some(Err(error)) if self.skip_bad_lines && error.is_line_error() => {
+                    self.skipped.push(error);
+                    continue;
+                }
+                Some(parsed) => return Some(parsed),
+                None => {}
+            }
+
+            if self.eof {
+                self.pb_readfile.finish();
+                if !self.skipped.is_empty() {
+                    Message::skipped_lines(&self.path, &self.skipped);
+                    self.skipped.clear();
+                }
+                return None;
+            }
+            match self.read_batch() {
+                Ok(batch) => self.parse_batch(batch),
+                Err(error) => {
+                    // nothing after can be read
+                    self.eof = true;
+                    return Some(Err(error));
+                }
+            }
+        }
     }
 }
 
-fn converter(activations: Vec<LineConceptX>) -> Vec<Line> {
-    let mut lines: Vec<Line> = Vec::new();
-    let mut pb_tokenlize =
-        ProgressBar::new(activations.len() as u64, constant::TOKEN_GENERATING, true);
-    for line in activations {
-        let mut tokens: Vec<Token> = Vec::new();
-        for feature_index in 0..line.features.len() {
-            let feature = line.features[feature_index].clone();
-            for token in feature.layers {
-                tokens.push(Token::new(
+fn converter(line: LineConceptX) -> Line {
+    let mut tokens: Vec<Token> = Vec::new();
+    for (feature_index, feature) in line.features.into_iter().enumerate() {
+        // one token per layer, `select_layers` picks the representation later
+        for token in feature.layers {
+            tokens.push(
+                Token::new(
                     String::from(&feature.token),
-                    line.linex_index,
                     feature_index,
+                    line.linex_index,
                     token.values,
-                ));
-            }
+                )
+                .with_layer(Some(token.index)),
+            );
         }
-        assert_eq!(tokens.len(), line.features.len());
-        lines.push(Line {
-            tokens,
-            line_num: line.linex_index,
-        });
-        pb_tokenlize.inc(1);
-    }
-    pb_tokenlize.finish();
-    lines
+    }
+    Line {
+        tokens,
+        line_num: line.linex_index,
+    }
 }
 
 #[cfg(test)]
@@ -102,14 +224,18 @@ mod test {
     #[test]
     fn test_read_with_correct_number() {
         let reader = ConceptXReader::new();
-        let lines = reader.read("./test_data/conceptx.json", false);
+        let lines = reader
+            .read("./test_data/conceptx.json", &ReadOptions::default())
+            .unwrap();
         assert_eq!(lines.len(), 10);
     }
 
     #[test]
     fn test_read_values() {
         let reader = ConceptXReader::new();
-        let lines = reader.read("./test_data/conceptx.json", false);
+        let lines = reader
+            .read("./test_data/conceptx.json", &ReadOptions::default())
+            .unwrap();
         assert_eq!(lines.len(), 10);
         println!("{}", lines[0].tokens[0].word);
         assert_eq!(lines[0].tokens[0].word, "[CLS]0");
@@ -121,10 +247,112 @@ mod test {
     #[test]
     fn test_read_values2() {
         let reader = ConceptXReader::new();
-        let lines = reader.read("./test_data/conceptx.json", false);
+        let lines = reader
+            .read("./test_data/conceptx.json", &ReadOptions::default())
+            .unwrap();
         assert_eq!(lines.len(), 10);
         assert_eq!(lines[1].tokens[0].word, "[CLS]1");
         assert_eq!(lines[1].tokens[0].line_num, 1);
         assert_eq!(lines[1].tokens[0].position, 0);
     }
+
+    #[test]
+    fn test_read_multiple_layers() {
+        let reader = ConceptXReader::new();
+        let lines = reader
+            .read("./test_data/conceptx_layers.json", &ReadOptions::default())
+            .unwrap();
+        assert_eq!(lines.len(), 3);
+        // "he is a nurse" with 3 layers per token
+        assert_eq!(lines[0].tokens.len(), 12);
+        assert_eq!(lines[0].tokens[2].layer, Some(2));
+        assert_eq!(lines[0].tokens[3].word, "is");
+        assert_eq!(lines[0].tokens[3].position, 1);
+        assert_eq!(lines[0].tokens[3].layer, Some(0));
+    }
+
+    #[test]
+    fn test_stream_matches_read() {
+        let reader = ConceptXReader::new();
+        let lines = reader
+            .read("./test_data/conceptx.json", &ReadOptions::default())
+            .unwrap();
+        let mut count = 0;
+        let stream = reader
+            .stream("./test_data/conceptx.json", &ReadOptions::default())
+            .unwrap();
+        for (streamed, read) in stream.zip(&lines) {
+            let streamed = streamed.unwrap();
+            assert_eq!(streamed.line_num, read.line_num);
+            assert_eq!(streamed.tokens.len(), read.tokens.len());
+            count += 1;
+        }
+        assert_eq!(count, lines.len());
+    }
+
+    #[test]
+    fn test_read_bad_lines() {
+        let reader = ConceptXReader::new();
+        let error = reader
+            .read("./test_data/conceptx_bad.json", &ReadOptions::default())
+            .unwrap_err();
+        match error {
+            ReadError::Parse { line, offset, .. } => {
+                assert_eq!(line, 2);
+                assert!(offset > 0);
+            }
+            _ => panic!("expected a parse error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn test_skip_bad_lines() {
+        let reader = ConceptXReader::new();
+        let options = ReadOptions {
+            skip_bad_lines: true,
+            ..ReadOptions::default()
+        };
+        let lines = reader
+            .read("./test_data/conceptx_bad.json", &options)
+            .unwrap();
+        // the broken JSON line and the line with a wrong dimension are skipped
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].line_num, 4);
+    }
+
+    #[test]
+    fn test_read_compressed() {
+        let reader = ConceptXReader::new();
+        for path in [
+            "./test_data/conceptx.json.gz",
+            "./test_data/conceptx.json.zst",
+        ] {
+            let lines = reader.read(path, &ReadOptions::default()).unwrap();
+            assert_eq!(lines.len(), 10);
+            assert_eq!(lines[1].tokens[0].word, "[CLS]1");
+        }
+    }
+
+    #[test]
+    fn test_parallel_keeps_order() {
+        let reader = ConceptXReader::new();
+        for threads in [1, 4] {
+            let options = ReadOptions {
+                threads: Some(threads),
+                ..ReadOptions::default()
+            };
+            let lines = reader.read("./test_data/conceptx.json", &options).unwrap();
+            let line_nums: Vec<usize> = lines.iter().map(|line| line.line_num).collect();
+            assert_eq!(line_nums, (0..10).collect::<Vec<usize>>());
+        }
+    }
+
+    #[test]
+    fn test_read_missing_file() {
+        let reader = ConceptXReader::new();
+        let error = reader
+            .read("./test_data/missing.json", &ReadOptions::default())
+            .unwrap_err();
+        assert!(!error.is_line_error());
+    }
 }
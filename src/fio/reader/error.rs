@@ -0,0 +1,99 @@
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::{create_exception, PyErr};
+use std::fmt;
+
+create_exception!(wafflecone, ParseError, PyValueError);
+create_exception!(wafflecone, DimensionError, PyValueError);
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    Parse {
+        path: String,
+        line: usize,
+        offset: u64,
+        message: String,
+    },
+    Dimension {
+        path: String,
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The layers asked for are not all there for a token
+    Layer {
+        line: usize,
+        word: String,
+        message: String,
+    },
+}
+
+impl ReadError {
+    pub fn io(path: &str, source: std::io::Error) -> Self {
+        ReadError::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    /// Errors that only concern one line and can be skipped
+    pub fn is_line_error(&self) -> bool {
+        !matches!(self, ReadError::Io { .. })
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io { path, source } => write!(f, "{}: {}", path, source),
+            ReadError::Parse {
+                path,
+                line,
+                offset,
+                message,
+            } => write!(
+                f,
+                "{}:{} (byte {}): invalid line: {}",
+                path, line, offset, message
+            ),
+            ReadError::Dimension {
+                path,
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}:{}: expected embeddings of dimension {}, found {}",
+                path, line, expected, found
+            ),
+            ReadError::Layer {
+                line,
+                word,
+                message,
+            } => write!(f, "line {}: token `{}`: {}", line, word, message),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<ReadError> for PyErr {
+    fn from(error: ReadError) -> PyErr {
+        match error {
+            ReadError::Io { .. } => PyIOError::new_err(error.to_string()),
+            ReadError::Parse { .. } => ParseError::new_err(error.to_string()),
+            ReadError::Dimension { .. } => DimensionError::new_err(error.to_string()),
+            ReadError::Layer { .. } => PyValueError::new_err(error.to_string()),
+        }
+    }
+}
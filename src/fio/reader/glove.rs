@@ -0,0 +1,36 @@
+use super::error::ReadError;
+use super::word2vec::TextVectorLines;
+use super::{ReadOptions, Reader};
+
+/// GloVe text vectors: `word v1 ... vdim` per line, without a header
+#[derive(Debug, Clone)]
+pub struct GloveReader {}
+
+impl Reader for GloveReader {
+    type Lines = TextVectorLines;
+
+    fn new() -> Self {
+        GloveReader {}
+    }
+
+    fn stream(&self, path: &str, options: &ReadOptions) -> Result<TextVectorLines, ReadError> {
+        TextVectorLines::open(path, options, false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_glove() {
+        let reader = GloveReader::new();
+        let lines = reader
+            .read("./test_data/glove.txt", &ReadOptions::default())
+            .unwrap();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].tokens[0].word, "he");
+        assert_eq!(lines[0].tokens[0].embedding, vec![1.0, 0.0, 0.5]);
+        assert_eq!(lines[0].tokens[0].layer, None);
+    }
+}
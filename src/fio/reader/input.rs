@@ -0,0 +1,113 @@
+use super::error::ReadError;
+use crate::util::constant;
+use crate::util::progress_bar::ProgressBar;
+use std::io::{BufRead, Read, Seek};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the compression from the first bytes of the content, not the file name
+    pub fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if magic.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// The path without a `.gz` or `.zst` suffix, to guess the format from the extension
+pub fn strip_compression_extension(path: &str) -> &str {
+    path.strip_suffix(".gz")
+        .or_else(|| path.strip_suffix(".zst"))
+        .unwrap_or(path)
+}
+
+/// Counts the bytes read from the inner reader on the progress bar
+pub struct ProgressReader<R> {
+    inner: R,
+    pb_readfile: ProgressBar,
+}
+
+impl<R: Read> ProgressReader<R> {
+    pub fn new(inner: R, pb_readfile: ProgressBar) -> Self {
+        ProgressReader { inner, pb_readfile }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let bytes = self.inner.read(buf)?;
+        self.pb_readfile.inc(bytes as u64);
+        Ok(bytes)
+    }
+}
+
+/// Open a file, transparently decompressing gzip and zstd content.
+///
+/// The returned progress bar follows the bytes read from the file, i.e. the
+/// compressed bytes, so it ends at the file size; the readers only finish it.
+pub fn open(path: &str, user_friendly: bool) -> Result<(Box<dyn BufRead>, ProgressBar), ReadError> {
+    let mut file = std::fs::File::open(path).map_err(|error| ReadError::io(path, error))?;
+    let file_size = file
+        .metadata()
+        .map_err(|error| ReadError::io(path, error))?
+        .len();
+
+    let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+    (&mut file)
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)
+        .map_err(|error| ReadError::io(path, error))?;
+    file.rewind().map_err(|error| ReadError::io(path, error))?;
+
+    let pb_readfile = ProgressBar::new(file_size, constant::FILE_READING, user_friendly);
+    let file = ProgressReader::new(file, pb_readfile.clone());
+    let reader: Box<dyn BufRead> = match Compression::detect(&magic) {
+        Compression::None => Box::new(std::io::BufReader::new(file)),
+        // multi member, as written by `cat a.gz b.gz` or pigz
+        Compression::Gzip => Box::new(std::io::BufReader::new(flate2::read::MultiGzDecoder::new(
+            file,
+        ))),
+        Compression::Zstd => Box::new(std::io::BufReader::new(
+            zstd::stream::read::Decoder::new(file).map_err(|error| ReadError::io(path, error))?,
+        )),
+    };
+    Ok((reader, pb_readfile))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn read_to_string(path: &str) -> String {
+        let (mut reader, _) = open(path, false).unwrap();
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn test_open_compressed() {
+        let plain = read_to_string("./test_data/conceptx.json");
+        assert_eq!(read_to_string("./test_data/conceptx.json.gz"), plain);
+        assert_eq!(read_to_string("./test_data/conceptx.json.zst"), plain);
+    }
+
+    #[test]
+    fn test_strip_compression_extension() {
+        assert_eq!(strip_compression_extension("a.json.zst"), "a.json");
+        assert_eq!(strip_compression_extension("a.txt.gz"), "a.txt");
+        assert_eq!(strip_compression_extension("a.npy"), "a.npy");
+    }
+}
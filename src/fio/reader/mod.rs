@@ -1,8 +1,460 @@
 pub mod conceptx;
+pub mod error;
+pub mod glove;
+pub mod input;
+pub mod numpy;
+pub mod word2vec;
 
 use crate::embedding::models::Line;
+use crate::embedding::normalizer::Normalizer;
+use crate::fio::cache;
+use crate::util::constant;
+use conceptx::ConceptXReader;
+use error::ReadError;
+use glove::GloveReader;
+use numpy::NumpyReader;
+use pyo3::{FromPyObject, PyAny, PyErr, PyResult};
+use std::io::BufRead;
+use std::path::Path;
+use word2vec::{Word2VecBinaryReader, Word2VecTextReader};
+
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    pub user_friendly: bool,
+    /// Skip lines that cannot be parsed instead of failing, they are reported at the end
+    pub skip_bad_lines: bool,
+    /// The token list of readers that only store the embeddings, e.g. `.npy` arrays, or
+    /// the directory of the token lists of several arrays
+    pub vocab_path: Option<String>,
+    /// Applied to every token read, see `stream_lines` and `read_lines`
+    pub normalizer: Normalizer,
+    /// Threads parsing the ConceptX lines, all cores when `None`
+    pub threads: Option<usize>,
+    /// Read the lines from the binary cache next to the file, written on the first read
+    pub cache: bool,
+}
 
 pub trait Reader {
+    /// Lines yielded one at a time, so large files never have to fit in memory
+    type Lines: Iterator<Item = Result<Line, ReadError>>;
+
     fn new() -> Self;
-    fn read(&self, path: &str, user_friendly: bool) -> Vec<Line>;
+    fn stream(&self, path: &str, options: &ReadOptions) -> Result<Self::Lines, ReadError>;
+
+    #[allow(dead_code)]
+    fn read(&self, path: &str, options: &ReadOptions) -> Result<Vec<Line>, ReadError> {
+        self.stream(path, options)?.collect()
+    }
+
+    /// Read several files, e.g. shards, one after the other. Every token keeps the index
+    /// of its file in `paths` as `source_id`.
+    #[allow(dead_code)]
+    fn read_all(&self, paths: &[String], options: &ReadOptions) -> Result<Vec<Line>, ReadError> {
+        let mut lines = Vec::new();
+        for (source_id, path) in paths.iter().enumerate() {
+            for mut line in self.read(path, options)? {
+                set_source(&mut line, source_id);
+                lines.push(line);
+            }
+        }
+        Ok(lines)
+    }
+}
+
+fn set_source(line: &mut Line, source_id: usize) {
+    for token in &mut line.tokens {
+        token.source_id = source_id;
+        token.update_id();
+    }
+}
+
+/// The input of a calculator: a file, a directory, a glob pattern, or a list of them
+#[derive(Debug, Clone)]
+pub struct InputPaths(pub Vec<String>);
+
+impl<'a> FromPyObject<'a> for InputPaths {
+    fn extract(obj: &'a PyAny) -> PyResult<Self> {
+        if let Ok(path) = obj.extract::<String>() {
+            Ok(InputPaths(vec![path]))
+        } else if let Ok(paths) = obj.extract::<Vec<String>>() {
+            Ok(InputPaths(paths))
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Invalid type for path, expected str or list of str",
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for InputPaths {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.join(", "))
+    }
+}
+
+impl InputPaths {
+    /// The files to read, in order: the files of a directory and the matches of a glob
+    /// pattern are sorted by name. Hidden files, caches and `.tsv` sidecars of a
+    /// directory are left out.
+    pub fn resolve(&self) -> Result<Vec<String>, ReadError> {
+        let mut files = Vec::new();
+        for input in &self.0 {
+            let mut matches: Vec<String> = if Path::new(input).is_dir() {
+                std::fs::read_dir(input)
+                    .map_err(|error| ReadError::io(input, error))?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.is_file())
+                    .map(|path| path.to_string_lossy().to_string())
+                    .filter(|path| {
+                        let name = Path::new(path)
+                            .file_name()
+                            .map(|name| name.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        !name.starts_with('.')
+                            && !name.ends_with(constant::CACHE_EXTENSION)
+                            && !name.ends_with(".tsv")
+                    })
+                    .collect()
+            } else if input.contains(['*', '?', '[']) {
+                glob::glob(input)
+                    .map_err(|error| ReadError::Parse {
+                        path: input.clone(),
+                        line: 0,
+                        offset: error.pos as u64,
+                        message: format!("invalid glob pattern: {}", error.msg),
+                    })?
+                    .filter_map(|path| path.ok())
+                    .filter(|path| path.is_file())
+                    .map(|path| path.to_string_lossy().to_string())
+                    .filter(|path| !path.ends_with(constant::CACHE_EXTENSION))
+                    .collect()
+            } else {
+                vec![input.clone()]
+            };
+            if matches.is_empty() {
+                return Err(ReadError::io(
+                    input,
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "no input file found"),
+                ));
+            }
+            matches.sort();
+            files.extend(matches);
+        }
+        Ok(files)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReaderType {
+    ConceptX,
+    Numpy,
+    Word2VecText,
+    Word2VecBinary,
+    GloVe,
+}
+
+impl<'a> FromPyObject<'a> for ReaderType {
+    fn extract(obj: &'a PyAny) -> PyResult<Self> {
+        if let Ok(string) = obj.extract::<&str>() {
+            match string {
+                "conceptx" => Ok(ReaderType::ConceptX),
+                "numpy" => Ok(ReaderType::Numpy),
+                "word2vec" => Ok(ReaderType::Word2VecText),
+                "word2vec_binary" => Ok(ReaderType::Word2VecBinary),
+                "glove" => Ok(ReaderType::GloVe),
+                _ => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid reader: {}",
+                    string
+                ))),
+            }
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Invalid type for reader, expected str",
+            ))
+        }
+    }
+}
+
+impl ReaderType {
+    /// Guess the reader from the file extension, ConceptX JSON by default.
+    /// `.txt` vectors are word2vec when the first line is a `count dim` header, GloVe otherwise.
+    /// A `.gz` or `.zst` suffix is ignored, e.g. `.jsonl.gz` is ConceptX.
+    pub fn detect(path: &str) -> Self {
+        let name = input::strip_compression_extension(path);
+        if name.ends_with(".npy") || name.ends_with(".npz") {
+            ReaderType::Numpy
+        } else if name.ends_with(".bin") {
+            ReaderType::Word2VecBinary
+        } else if name.ends_with(".vec") {
+            ReaderType::Word2VecText
+        } else if name.ends_with(".txt") {
+            let mut first_line = String::new();
+            if let Ok((mut reader, _)) = input::open(path, false) {
+                let _ = reader.read_line(&mut first_line);
+            }
+            match word2vec::parse_header(&first_line) {
+                Some(_) => ReaderType::Word2VecText,
+                None => ReaderType::GloVe,
+            }
+        } else {
+            ReaderType::ConceptX
+        }
+    }
+}
+
+pub type LineStream = Box<dyn Iterator<Item = Result<Line, ReadError>>>;
+
+/// Stream the lines of any reader, as the reader returns them
+fn raw_lines(
+    path: &str,
+    reader_type: ReaderType,
+    options: &ReadOptions,
+) -> Result<LineStream, ReadError> {
+    Ok(match reader_type {
+        ReaderType::ConceptX => Box::new(ConceptXReader::new().stream(path, options)?),
+        ReaderType::Numpy => Box::new(NumpyReader::new().stream(path, options)?),
+        ReaderType::Word2VecText => Box::new(Word2VecTextReader::new().stream(path, options)?),
+        ReaderType::Word2VecBinary => Box::new(Word2VecBinaryReader::new().stream(path, options)?),
+        ReaderType::GloVe => Box::new(GloveReader::new().stream(path, options)?),
+    })
+}
+
+/// Stream the lines of any reader, with their tokens normalized
+pub fn stream_lines(
+    path: &str,
+    reader_type: ReaderType,
+    options: &ReadOptions,
+) -> Result<LineStream, ReadError> {
+    let lines = if options.cache {
+        cache::cached_lines(path, reader_type, options, || {
+            raw_lines(path, reader_type, options)
+        })?
+    } else {
+        raw_lines(path, reader_type, options)?
+    };
+    let normalizer = options.normalizer.clone();
+    Ok(Box::new(lines.map(move |line| {
+        line.map(|mut line| {
+            normalizer.normalize_line(&mut line);
+            line
+        })
+    })))
+}
+
+/// Read the lines of any reader, with their tokens normalized
+#[allow(dead_code)]
+pub fn read_lines(
+    path: &str,
+    reader_type: ReaderType,
+    options: &ReadOptions,
+) -> Result<Vec<Line>, ReadError> {
+    stream_lines(path, reader_type, options)?.collect()
+}
+
+/// Stream the lines of several files one after the other, see `Reader::read_all`.
+/// The reader is guessed for every file when not given; the files are opened lazily.
+pub fn stream_sources(
+    paths: &[String],
+    reader_type: Option<ReaderType>,
+    options: &ReadOptions,
+) -> LineStream {
+    if let Err(error) = check_vocab_path(paths, reader_type, options) {
+        return Box::new(std::iter::once(Err(error)));
+    }
+    let paths = paths.to_vec();
+    let options = options.clone();
+    Box::new(
+        paths
+            .into_iter()
+            .enumerate()
+            .flat_map(move |(source_id, path)| {
+                let reader_type = reader_type.unwrap_or_else(|| ReaderType::detect(&path));
+                match stream_lines(&path, reader_type, &options) {
+                    Ok(lines) => Box::new(lines.map(move |line| {
+                        line.map(|mut line| {
+                            set_source(&mut line, source_id);
+                            line
+                        })
+                    })) as LineStream,
+                    Err(error) => Box::new(std::iter::once(Err(error))),
+                }
+            }),
+    )
+}
+
+/// A single token list cannot list the rows of several arrays
+fn check_vocab_path(
+    paths: &[String],
+    reader_type: Option<ReaderType>,
+    options: &ReadOptions,
+) -> Result<(), ReadError> {
+    let vocab_path = match &options.vocab_path {
+        Some(vocab_path) if !Path::new(vocab_path).is_dir() => vocab_path,
+        _ => return Ok(()),
+    };
+    let arrays = paths
+        .iter()
+        .filter(|path| reader_type.unwrap_or_else(|| ReaderType::detect(path)) == ReaderType::Numpy)
+        .count();
+    if arrays > 1 {
+        return Err(ReadError::Parse {
+            path: vocab_path.clone(),
+            line: 0,
+            offset: 0,
+            message: format!(
+                "one token list is given for {} arrays, give a directory of `<array name>.tsv` files instead",
+                arrays
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Fail on files that cannot be read a second time, e.g. a pipe such as `/dev/stdin`
+pub fn check_rereadable(paths: &[String]) -> Result<(), ReadError> {
+    for path in paths {
+        let metadata = std::fs::metadata(path).map_err(|error| ReadError::io(path, error))?;
+        if !metadata.is_file() {
+            return Err(ReadError::io(
+                path,
+                std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "streaming reads the input twice and needs a regular file, not a pipe",
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn read_sources(
+    paths: &[String],
+    reader_type: Option<ReaderType>,
+    options: &ReadOptions,
+) -> Result<Vec<Line>, ReadError> {
+    stream_sources(paths, reader_type, options).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_rereadable() {
+        assert!(check_rereadable(&["./test_data/conceptx.json".to_string()]).is_ok());
+        assert!(check_rereadable(&["/dev/null".to_string()]).is_err());
+        assert!(check_rereadable(&["./test_data/missing.json".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_detect_reader() {
+        assert_eq!(
+            ReaderType::detect("./test_data/conceptx.json"),
+            ReaderType::ConceptX
+        );
+        assert_eq!(
+            ReaderType::detect("./test_data/bundle.npz"),
+            ReaderType::Numpy
+        );
+        assert_eq!(
+            ReaderType::detect("./test_data/word2vec.txt"),
+            ReaderType::Word2VecText
+        );
+        assert_eq!(
+            ReaderType::detect("./test_data/word2vec.bin"),
+            ReaderType::Word2VecBinary
+        );
+        assert_eq!(
+            ReaderType::detect("./test_data/glove.txt"),
+            ReaderType::GloVe
+        );
+        assert_eq!(
+            ReaderType::detect("./test_data/glove.txt.gz"),
+            ReaderType::GloVe
+        );
+        assert_eq!(
+            ReaderType::detect("./test_data/conceptx.json.zst"),
+            ReaderType::ConceptX
+        );
+    }
+
+    #[test]
+    fn test_resolve_inputs() {
+        let paths = InputPaths(vec!["./test_data/conceptx*.json".to_string()])
+            .resolve()
+            .unwrap();
+        let names: Vec<&str> = paths
+            .iter()
+            .map(|path| path.rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["conceptx.json", "conceptx_bad.json", "conceptx_layers.json"]
+        );
+        let paths = InputPaths(vec!["./test_data".to_string()])
+            .resolve()
+            .unwrap();
+        assert!(paths.contains(&"./test_data/embeddings.npy".to_string()));
+        assert!(!paths.contains(&"./test_data/embeddings.tsv".to_string()));
+        assert!(InputPaths(vec!["./test_data/*.missing".to_string()])
+            .resolve()
+            .is_err());
+    }
+
+    #[test]
+    fn test_read_sources() {
+        let paths = vec![
+            "./test_data/conceptx.json".to_string(),
+            "./test_data/conceptx_layers.json".to_string(),
+        ];
+        let lines = read_sources(&paths, None, &ReadOptions::default()).unwrap();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[9].tokens[0].source_id, 0);
+        assert_eq!(lines[10].tokens[0].source_id, 1);
+
+        let lines = ConceptXReader::new()
+            .read_all(&paths, &ReadOptions::default())
+            .unwrap();
+        assert_eq!(lines[12].tokens[0].source_id, 1);
+    }
+
+    #[test]
+    fn test_token_ids_are_unique_across_sources() {
+        let paths = vec![
+            "./test_data/embeddings.npy".to_string(),
+            "./test_data/embeddings.npy".to_string(),
+        ];
+        let options = ReadOptions {
+            vocab_path: Some("./test_data/embeddings.tsv".to_string()),
+            ..ReadOptions::default()
+        };
+        assert!(matches!(
+            read_sources(&paths, None, &options),
+            Err(ReadError::Parse { .. })
+        ));
+
+        // a directory gives each array its own token list
+        let options = ReadOptions {
+            vocab_path: Some("./test_data".to_string()),
+            ..ReadOptions::default()
+        };
+        let lines = read_sources(&paths, None, &options).unwrap();
+        let (first, second) = lines.split_at(lines.len() / 2);
+        assert_eq!(first[0].tokens[0].word, second[0].tokens[0].word);
+        assert_ne!(first[0].tokens[0].token_id, second[0].tokens[0].token_id);
+    }
+
+    #[test]
+    fn test_read_lines_normalized() {
+        let lines = read_lines(
+            "./test_data/conceptx.json",
+            ReaderType::ConceptX,
+            &ReadOptions::default(),
+        )
+        .unwrap();
+        // "NEW YORK ##ER"
+        assert_eq!(lines[0].tokens[3].raw_word, "##ER");
+        assert_eq!(lines[0].tokens[3].word, "ER");
+        assert!(lines[0].tokens[3].continuation);
+    }
 }
@@ -0,0 +1,431 @@
+use super::error::ReadError;
+use super::input::{self, ProgressReader};
+use super::{ReadOptions, Reader};
+use crate::embedding::models::{Line, Token};
+use crate::util::constant;
+use crate::util::progress_bar::ProgressBar;
+use std::io::{BufRead, Read, Seek};
+use std::path::Path;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+/// The array used when a `.npz` bundle holds more than one array
+const NPZ_EMBEDDINGS: &str = "embeddings.npy";
+
+/// Reads a `(tokens, dim)` or `(tokens, layers, dim)` float array from a `.npy` file or a
+/// `.npz` bundle, with a sidecar file giving the token of every row.
+///
+/// The sidecar has one row per token: either `word` alone (every token is its own line)
+/// or `word<TAB>line_num<TAB>position`. By default it is the array path with a `.tsv`
+/// extension.
+#[derive(Debug, Clone)]
+pub struct NumpyReader {}
+
+impl Reader for NumpyReader {
+    type Lines = NumpyLines;
+
+    fn new() -> Self {
+        NumpyReader {}
+    }
+
+    fn stream(&self, path: &str, options: &ReadOptions) -> Result<NumpyLines, ReadError> {
+        let vocab_path = vocab_path(path, options);
+        let vocab = read_vocab(&vocab_path)?;
+
+        let (mut data, pb_readfile): (Box<dyn Read>, ProgressBar) =
+            if input::strip_compression_extension(path).ends_with(".npz") {
+                let bytes = read_npz_entry(path)?;
+                let pb_readfile = ProgressBar::new(
+                    bytes.len() as u64,
+                    constant::FILE_READING,
+                    options.user_friendly,
+                );
+                let data = ProgressReader::new(std::io::Cursor::new(bytes), pb_readfile.clone());
+                (Box::new(data), pb_readfile)
+            } else {
+                let (data, pb_readfile) = input::open(path, options.user_friendly)?;
+                (Box::new(data), pb_readfile)
+            };
+        let header = read_header(&mut data, path)?;
+
+        if header.rows != vocab.len() {
+            return Err(ReadError::Parse {
+                path: vocab_path,
+                line: vocab.len(),
+                offset: 0,
+                message: format!(
+                    "the array has {} rows but {} tokens are listed",
+                    header.rows,
+                    vocab.len()
+                ),
+            });
+        }
+
+        Ok(NumpyLines {
+            path: path.to_string(),
+            data,
+            header,
+            vocab: vocab.into_iter().peekable(),
+            pb_readfile,
+        })
+    }
+}
+
+struct VocabEntry {
+    word: String,
+    line_num: usize,
+    position: usize,
+}
+
+/// The sidecar of the array, `<path>.tsv` unless given in the options. A directory given
+/// in the options holds the sidecars of several arrays, by the same name.
+pub(crate) fn vocab_path(path: &str, options: &ReadOptions) -> String {
+    let sidecar = Path::new(input::strip_compression_extension(path)).with_extension("tsv");
+    match &options.vocab_path {
+        Some(vocab_path) if Path::new(vocab_path).is_dir() => match sidecar.file_name() {
+            Some(name) => Path::new(vocab_path)
+                .join(name)
+                .to_string_lossy()
+                .to_string(),
+            None => vocab_path.clone(),
+        },
+        Some(vocab_path) => vocab_path.clone(),
+        None => sidecar.to_string_lossy().to_string(),
+    }
+}
+
+fn read_vocab(path: &str) -> Result<Vec<VocabEntry>, ReadError> {
+    let (file, _) = input::open(path, false)?;
+    let mut vocab = Vec::new();
+    let mut offset = 0;
+    for (index, line) in file.lines().enumerate() {
+        let line = line.map_err(|error| ReadError::io(path, error))?;
+        let bad_line = |message: &str| ReadError::Parse {
+            path: path.to_string(),
+            line: index + 1,
+            offset,
+            message: message.to_string(),
+        };
+        let fields: Vec<&str> = line.split('\t').collect();
+        let entry = match fields.as_slice() {
+            [word] => VocabEntry {
+                word: word.to_string(),
+                line_num: index,
+                position: 0,
+            },
+            [word, line_num, position] => VocabEntry {
+                word: word.to_string(),
+                line_num: line_num
+                    .parse()
+                    .map_err(|_| bad_line("the line number is not an integer"))?,
+                position: position
+                    .parse()
+                    .map_err(|_| bad_line("the position is not an integer"))?,
+            },
+            _ => return Err(bad_line("expected `word` or `word<TAB>line<TAB>position`")),
+        };
+        vocab.push(entry);
+        offset += line.len() as u64 + 1;
+    }
+    Ok(vocab)
+}
+
+/// The embeddings of a `.npz` bundle. A compressed bundle is decompressed in memory
+/// first, as the archive is read from its end.
+fn read_npz_entry(path: &str) -> Result<Vec<u8>, ReadError> {
+    if input::strip_compression_extension(path) == path {
+        let file = std::fs::File::open(path).map_err(|error| ReadError::io(path, error))?;
+        return read_npz_archive(file, path);
+    }
+    let (mut reader, _) = input::open(path, false)?;
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|error| ReadError::io(path, error))?;
+    read_npz_archive(std::io::Cursor::new(bytes), path)
+}
+
+fn read_npz_archive<R: Read + Seek>(bundle: R, path: &str) -> Result<Vec<u8>, ReadError> {
+    let bad_bundle = |message: String| ReadError::Parse {
+        path: path.to_string(),
+        line: 0,
+        offset: 0,
+        message,
+    };
+    let mut archive =
+        zip::ZipArchive::new(bundle).map_err(|error| bad_bundle(error.to_string()))?;
+    let name = if archive.len() == 1 {
+        archive.name_for_index(0).unwrap_or_default().to_string()
+    } else {
+        NPZ_EMBEDDINGS.to_string()
+    };
+    let mut entry = archive.by_name(&name).map_err(|_| {
+        bad_bundle(format!(
+            "the bundle holds several arrays, the embeddings must be saved as `{}`",
+            NPZ_EMBEDDINGS.trim_end_matches(".npy")
+        ))
+    })?;
+    let mut bytes = Vec::new();
+    entry
+        .read_to_end(&mut bytes)
+        .map_err(|error| ReadError::io(path, error))?;
+    Ok(bytes)
+}
+
+#[derive(Debug)]
+struct NpyHeader {
+    rows: usize,
+    layers: Option<usize>,
+    dimension: usize,
+    value_size: usize,
+    little_endian: bool,
+}
+
+impl NpyHeader {
+    fn row_len(&self) -> usize {
+        self.layers.unwrap_or(1) * self.dimension
+    }
+}
+
+fn read_header(data: &mut dyn Read, path: &str) -> Result<NpyHeader, ReadError> {
+    let bad_header = |offset: u64, message: &str| ReadError::Parse {
+        path: path.to_string(),
+        line: 0,
+        offset,
+        message: message.to_string(),
+    };
+
+    let mut preamble = [0u8; 8];
+    data.read_exact(&mut preamble)
+        .map_err(|error| ReadError::io(path, error))?;
+    if &preamble[..6] != MAGIC {
+        return Err(bad_header(0, "not a .npy file"));
+    }
+    // version 1 stores the header length on 2 bytes, later versions on 4
+    let header_len = if preamble[6] == 1 {
+        let mut len = [0u8; 2];
+        data.read_exact(&mut len)
+            .map_err(|error| ReadError::io(path, error))?;
+        u16::from_le_bytes(len) as usize
+    } else {
+        let mut len = [0u8; 4];
+        data.read_exact(&mut len)
+            .map_err(|error| ReadError::io(path, error))?;
+        u32::from_le_bytes(len) as usize
+    };
+    let mut header = vec![0u8; header_len];
+    data.read_exact(&mut header)
+        .map_err(|error| ReadError::io(path, error))?;
+    let header = String::from_utf8_lossy(&header);
+
+    let descr = header_value(&header, "descr")
+        .map(|value| value.trim_matches(|c| c == '\'' || c == '"'))
+        .ok_or_else(|| bad_header(8, "the header has no `descr`"))?;
+    let (little_endian, value_size) = match descr {
+        "<f4" | "=f4" => (true, 4),
+        "<f8" | "=f8" => (true, 8),
+        ">f4" => (false, 4),
+        ">f8" => (false, 8),
+        _ => {
+            return Err(bad_header(
+                8,
+                &format!("unsupported dtype `{}`, expected float32 or float64", descr),
+            ))
+        }
+    };
+
+    if header_value(&header, "fortran_order") == Some("True") {
+        return Err(bad_header(
+            8,
+            "Fortran ordered arrays are not supported, save a C contiguous array",
+        ));
+    }
+
+    let shape: Vec<usize> = header_value(&header, "shape")
+        .map(|value| {
+            value
+                .trim_matches(|c| c == '(' || c == ')')
+                .split(',')
+                .map(|dimension| dimension.trim())
+                .filter(|dimension| !dimension.is_empty())
+                .map(|dimension| dimension.parse::<usize>())
+                .collect::<Result<Vec<usize>, _>>()
+        })
+        .and_then(|shape| shape.ok())
+        .ok_or_else(|| bad_header(8, "the header has no valid `shape`"))?;
+    let (rows, layers, dimension) = match shape.as_slice() {
+        [rows, dimension] => (*rows, None, *dimension),
+        [rows, layers, dimension] => (*rows, Some(*layers), *dimension),
+        _ => {
+            return Err(bad_header(
+                8,
+                "expected an array of shape (tokens, dim) or (tokens, layers, dim)",
+            ))
+        }
+    };
+
+    Ok(NpyHeader {
+        rows,
+        layers,
+        dimension,
+        value_size,
+        little_endian,
+    })
+}
+
+/// The raw value of `key` in the header, which is a Python dict literal
+fn header_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header
+        .find(&format!("'{}'", key))
+        .or_else(|| header.find(&format!("\"{}\"", key)))?;
+    let rest = header[start + key.len() + 2..].trim_start();
+    let rest = rest.strip_prefix(':')?.trim_start();
+    let end = if rest.starts_with('(') {
+        rest.find(')')? + 1
+    } else {
+        rest.find(',').unwrap_or(rest.len())
+    };
+    Some(rest[..end].trim())
+}
+
+/// Reads the array row by row, one line of the sidecar at a time
+pub struct NumpyLines {
+    path: String,
+    data: Box<dyn Read>,
+    header: NpyHeader,
+    vocab: std::iter::Peekable<std::vec::IntoIter<VocabEntry>>,
+    pb_readfile: ProgressBar,
+}
+
+impl NumpyLines {
+    fn read_row(&mut self) -> Result<Vec<f64>, ReadError> {
+        let mut bytes = vec![0u8; self.header.row_len() * self.header.value_size];
+        self.data
+            .read_exact(&mut bytes)
+            .map_err(|error| ReadError::io(&self.path, error))?;
+
+        let little_endian = self.header.little_endian;
+        let values = bytes
+            .chunks_exact(self.header.value_size)
+            .map(|chunk| match (chunk.len(), little_endian) {
+                (4, true) => f32::from_le_bytes(chunk.try_into().unwrap()) as f64,
+                (4, false) => f32::from_be_bytes(chunk.try_into().unwrap()) as f64,
+                (_, true) => f64::from_le_bytes(chunk.try_into().unwrap()),
+                (_, false) => f64::from_be_bytes(chunk.try_into().unwrap()),
+            })
+            .collect();
+        Ok(values)
+    }
+}
+
+impl Iterator for NumpyLines {
+    type Item = Result<Line, ReadError>;
+
+    fn next(&mut self) -> Option<Result<Line, ReadError>> {
+        let line_num = match self.vocab.peek() {
+            Some(entry) => entry.line_num,
+            None => {
+                self.pb_readfile.finish();
+                return None;
+            }
+        };
+
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Some(entry) = self.vocab.next_if(|entry| entry.line_num == line_num) {
+            let values = match self.read_row() {
+                Ok(values) => values,
+                Err(error) => return Some(Err(error)),
+            };
+            match self.header.layers {
+                None => tokens.push(Token::new(entry.word, entry.position, line_num, values)),
+                Some(_) => {
+                    for (layer, values) in values.chunks(self.header.dimension).enumerate() {
+                        tokens.push(
+                            Token::new(
+                                entry.word.clone(),
+                                entry.position,
+                                line_num,
+                                values.to_vec(),
+                            )
+                            .with_layer(Some(layer)),
+                        );
+                    }
+                }
+            }
+        }
+
+        Some(Ok(Line { tokens, line_num }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_npy() {
+        let reader = NumpyReader::new();
+        let lines = reader
+            .read("./test_data/embeddings.npy", &ReadOptions::default())
+            .unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].tokens.len(), 4);
+        assert_eq!(lines[0].tokens[3].word, "nurse");
+        assert_eq!(lines[0].tokens[3].embedding, vec![9.0, 10.0, 11.0]);
+        assert_eq!(lines[1].tokens[1].position, 1);
+        assert_eq!(lines[1].tokens[1].line_num, 1);
+    }
+
+    #[test]
+    fn test_read_npz_with_layers() {
+        let reader = NumpyReader::new();
+        let lines = reader
+            .read("./test_data/bundle.npz", &ReadOptions::default())
+            .unwrap();
+        // the sidecar only lists words, every token is its own line
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[5].tokens.len(), 2);
+        assert_eq!(lines[5].tokens[1].layer, Some(1));
+        assert_eq!(lines[5].tokens[1].embedding.len(), 3);
+    }
+
+    #[test]
+    fn test_read_compressed_npz() {
+        let dir = std::env::temp_dir().join(format!("wafflecone-npz-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bundle.npz.gz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        );
+        std::io::copy(
+            &mut std::fs::File::open("./test_data/bundle.npz").unwrap(),
+            &mut encoder,
+        )
+        .unwrap();
+        encoder.finish().unwrap();
+        std::fs::copy("./test_data/bundle.tsv", dir.join("bundle.tsv")).unwrap();
+
+        let path = path.to_string_lossy().to_string();
+        assert_eq!(
+            crate::fio::reader::ReaderType::detect(&path),
+            crate::fio::reader::ReaderType::Numpy
+        );
+        let lines = NumpyReader::new()
+            .read(&path, &ReadOptions::default())
+            .unwrap();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[5].tokens[1].embedding.len(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_vocab_mismatch() {
+        let reader = NumpyReader::new();
+        let options = ReadOptions {
+            vocab_path: Some("./test_data/conceptx.json".to_string()),
+            ..ReadOptions::default()
+        };
+        assert!(reader.read("./test_data/embeddings.npy", &options).is_err());
+    }
+}
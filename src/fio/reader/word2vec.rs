@@ -0,0 +1,296 @@
+use super::error::ReadError;
+use super::input;
+use super::{ReadOptions, Reader};
+use crate::embedding::models::{Line, Token};
+use crate::util::progress_bar::ProgressBar;
+use crate::util::Message;
+use std::io::{BufRead, Read};
+
+/// The word2vec text format: a `count dim` header, then `word v1 ... vdim` per line
+#[derive(Debug, Clone)]
+pub struct Word2VecTextReader {}
+
+/// The word2vec binary format: a `count dim` header line, then every word followed by a
+/// space and `dim` little endian float32 values
+#[derive(Debug, Clone)]
+pub struct Word2VecBinaryReader {}
+
+impl Reader for Word2VecTextReader {
+    type Lines = TextVectorLines;
+
+    fn new() -> Self {
+        Word2VecTextReader {}
+    }
+
+    fn stream(&self, path: &str, options: &ReadOptions) -> Result<TextVectorLines, ReadError> {
+        TextVectorLines::open(path, options, true)
+    }
+}
+
+impl Reader for Word2VecBinaryReader {
+    type Lines = BinaryVectorLines;
+
+    fn new() -> Self {
+        Word2VecBinaryReader {}
+    }
+
+    fn stream(&self, path: &str, options: &ReadOptions) -> Result<BinaryVectorLines, ReadError> {
+        let (mut reader, pb_readfile) = input::open(path, options.user_friendly)?;
+
+        let mut header = String::new();
+        reader
+            .read_line(&mut header)
+            .map_err(|error| ReadError::io(path, error))?;
+        let (count, dimension) = parse_header(&header).ok_or_else(|| ReadError::Parse {
+            path: path.to_string(),
+            line: 1,
+            offset: 0,
+            message: "expected a `count dim` header".to_string(),
+        })?;
+
+        Ok(BinaryVectorLines {
+            path: path.to_string(),
+            reader,
+            count,
+            dimension,
+            index: 0,
+            offset: header.len() as u64,
+            pb_readfile,
+        })
+    }
+}
+
+/// `count dim`, the first line of both word2vec formats
+pub(crate) fn parse_header(line: &str) -> Option<(usize, usize)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match fields.as_slice() {
+        [count, dimension] => Some((count.parse().ok()?, dimension.parse().ok()?)),
+        _ => None,
+    }
+}
+
+/// One vocabulary entry of a static embedding, which is its own line
+fn vocabulary_line(word: String, index: usize, embedding: Vec<f64>) -> Line {
+    Line {
+        tokens: vec![Token::new(word, 0, index, embedding)],
+        line_num: index,
+    }
+}
+
+/// Text vectors, shared by word2vec (with a header) and GloVe (without one)
+pub struct TextVectorLines {
+    path: String,
+    reader: Box<dyn BufRead>,
+    skip_bad_lines: bool,
+    dimension: Option<usize>,
+    line_number: usize,
+    index: usize,
+    offset: u64,
+    skipped: Vec<ReadError>,
+    pb_readfile: ProgressBar,
+}
+
+impl TextVectorLines {
+    pub(crate) fn open(
+        path: &str,
+        options: &ReadOptions,
+        has_header: bool,
+    ) -> Result<TextVectorLines, ReadError> {
+        let (mut reader, pb_readfile) = input::open(path, options.user_friendly)?;
+
+        let mut dimension = None;
+        let mut offset = 0;
+        let mut line_number = 0;
+        if has_header {
+            let mut header = String::new();
+            reader
+                .read_line(&mut header)
+                .map_err(|error| ReadError::io(path, error))?;
+            let (_, header_dimension) = parse_header(&header).ok_or_else(|| ReadError::Parse {
+                path: path.to_string(),
+                line: 1,
+                offset: 0,
+                message: "expected a `count dim` header".to_string(),
+            })?;
+            dimension = Some(header_dimension);
+            offset = header.len() as u64;
+            line_number = 1;
+        }
+
+        Ok(TextVectorLines {
+            path: path.to_string(),
+            reader,
+            skip_bad_lines: options.skip_bad_lines,
+            dimension,
+            line_number,
+            index: 0,
+            offset,
+            skipped: Vec::new(),
+            pb_readfile,
+        })
+    }
+
+    fn parse(&mut self, line: &str) -> Result<Line, ReadError> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // without a header, the first line gives the dimension
+        let dimension = *self.dimension.get_or_insert(fields.len().saturating_sub(1));
+        if fields.len() <= dimension {
+            return Err(ReadError::Dimension {
+                path: self.path.clone(),
+                line: self.line_number,
+                expected: dimension,
+                found: fields.len().saturating_sub(1),
+            });
+        }
+
+        // words may contain spaces, the values are always the last `dimension` fields
+        let split = fields.len() - dimension;
+        let mut embedding: Vec<f64> = Vec::with_capacity(dimension);
+        for value in &fields[split..] {
+            embedding.push(value.parse().map_err(|_| ReadError::Parse {
+                path: self.path.clone(),
+                line: self.line_number,
+                offset: self.offset,
+                message: format!("`{}` is not a number", value),
+            })?);
+        }
+
+        let line = vocabulary_line(fields[..split].join(" "), self.index, embedding);
+        self.index += 1;
+        Ok(line)
+    }
+}
+
+impl Iterator for TextVectorLines {
+    type Item = Result<Line, ReadError>;
+
+    fn next(&mut self) -> Option<Result<Line, ReadError>> {
+        loop {
+            let mut line = String::new();
+            let bytes = match self.reader.read_line(&mut line) {
+                Ok(bytes) => bytes,
+                Err(error) => return Some(Err(ReadError::io(&self.path, error))),
+            };
+            if bytes == 0 {
+                self.pb_readfile.finish();
+                if !self.skipped.is_empty() {
+                    Message::skipped_lines(&self.path, &self.skipped);
+                    self.skipped.clear();
+                }
+                return None;
+            }
+            self.line_number += 1;
+
+            let parsed = if line.trim().is_empty() {
+                None
+            } else {
+                Some(self.parse(&line))
+            };
+            self.offset += bytes as u64;
+
+            match parsed {
+                None => continue,
+                Some(Err(error)) if self.skip_bad_lines && error.is_line_error() => {
+                    self.skipped.push(error)
+                }
+                Some(parsed) => return Some(parsed),
+            }
+        }
+    }
+}
+
+pub struct BinaryVectorLines {
+    path: String,
+    reader: Box<dyn BufRead>,
+    count: usize,
+    dimension: usize,
+    index: usize,
+    offset: u64,
+    pb_readfile: ProgressBar,
+}
+
+impl BinaryVectorLines {
+    fn read_entry(&mut self) -> Result<Line, ReadError> {
+        let mut word: Vec<u8> = Vec::new();
+        self.reader
+            .read_until(b' ', &mut word)
+            .map_err(|error| ReadError::io(&self.path, error))?;
+        let word_len = word.len();
+        // the previous vector may be followed by a newline
+        let word = String::from_utf8_lossy(&word)
+            .trim_matches(|c: char| c == ' ' || c == '\n')
+            .to_string();
+        if word.is_empty() {
+            return Err(ReadError::Parse {
+                path: self.path.clone(),
+                line: self.index + 2,
+                offset: self.offset,
+                message: format!(
+                    "expected {} words, the file ends after {}",
+                    self.count, self.index
+                ),
+            });
+        }
+
+        let mut values = vec![0u8; self.dimension * 4];
+        self.reader
+            .read_exact(&mut values)
+            .map_err(|error| ReadError::io(&self.path, error))?;
+        let embedding = values
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()) as f64)
+            .collect();
+
+        self.offset += (word_len + values.len()) as u64;
+
+        let line = vocabulary_line(word, self.index, embedding);
+        self.index += 1;
+        Ok(line)
+    }
+}
+
+impl Iterator for BinaryVectorLines {
+    type Item = Result<Line, ReadError>;
+
+    fn next(&mut self) -> Option<Result<Line, ReadError>> {
+        if self.index == self.count {
+            self.pb_readfile.finish();
+            return None;
+        }
+        let entry = self.read_entry();
+        if entry.is_err() {
+            // the position in the file is lost, nothing after can be read
+            self.index = self.count;
+        }
+        Some(entry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_word2vec_text() {
+        let reader = Word2VecTextReader::new();
+        let lines = reader
+            .read("./test_data/word2vec.txt", &ReadOptions::default())
+            .unwrap();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1].tokens[0].word, "she");
+        assert_eq!(lines[1].tokens[0].line_num, 1);
+        assert_eq!(lines[3].tokens[0].word, "new york");
+        assert_eq!(lines[3].tokens[0].embedding, vec![0.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_read_word2vec_binary() {
+        let reader = Word2VecBinaryReader::new();
+        let lines = reader
+            .read("./test_data/word2vec.bin", &ReadOptions::default())
+            .unwrap();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[2].tokens[0].word, "nurse");
+        assert_eq!(lines[2].tokens[0].embedding, vec![0.5, 0.5, -1.0]);
+    }
+}
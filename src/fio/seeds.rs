@@ -0,0 +1,184 @@
+use crate::fio::reader::error::ReadError;
+use crate::space::seeds::SubspaceSeeds;
+use pyo3::{FromPyObject, PyAny, PyErr, PyResult};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The formats of a seed file, each holding one or more groups
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeedFormat {
+    /// `{"male": ["he", ...], ...}` or `[{"name": "male", "seeds": ["he", ...]}, ...]`
+    Json,
+    /// `male = ["he", ...]`, one key per group
+    Toml,
+    /// A `[male]` header, then one seed per line. `#` starts a comment.
+    Text,
+}
+
+impl SeedFormat {
+    /// Guess the format from the extension, plain text by default
+    pub fn detect(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("json") => SeedFormat::Json,
+            Some("toml") => SeedFormat::Toml,
+            _ => SeedFormat::Text,
+        }
+    }
+}
+
+impl<'a> FromPyObject<'a> for SeedFormat {
+    fn extract(obj: &'a PyAny) -> PyResult<Self> {
+        if let Ok(string) = obj.extract::<&str>() {
+            match string {
+                "json" => Ok(SeedFormat::Json),
+                "toml" => Ok(SeedFormat::Toml),
+                "text" => Ok(SeedFormat::Text),
+                _ => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid enum variant: {}",
+                    string
+                ))),
+            }
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Invalid type for enum conversion",
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SeedGroups {
+    List(Vec<SubspaceSeeds>),
+    Map(BTreeMap<String, Vec<String>>),
+}
+
+impl From<SeedGroups> for Vec<SubspaceSeeds> {
+    fn from(groups: SeedGroups) -> Self {
+        match groups {
+            SeedGroups::List(groups) => groups,
+            SeedGroups::Map(groups) => groups
+                .into_iter()
+                .map(|(name, seeds)| SubspaceSeeds::new(name, seeds))
+                .collect(),
+        }
+    }
+}
+
+/// Read the groups of a seed file, sorted by name unless the file is a JSON list
+pub fn load_seeds(path: &str, format: Option<SeedFormat>) -> Result<Vec<SubspaceSeeds>, ReadError> {
+    let text = std::fs::read_to_string(path).map_err(|error| ReadError::io(path, error))?;
+    parse_seeds(
+        &text,
+        format.unwrap_or_else(|| SeedFormat::detect(path)),
+        path,
+    )
+}
+
+/// Parse the groups of a seed file, `origin` naming it in the errors
+pub fn parse_seeds(
+    text: &str,
+    format: SeedFormat,
+    origin: &str,
+) -> Result<Vec<SubspaceSeeds>, ReadError> {
+    let error = |line: usize, offset: usize, message: String| ReadError::Parse {
+        path: origin.to_string(),
+        line,
+        offset: offset as u64,
+        message,
+    };
+    match format {
+        SeedFormat::Json => serde_json::from_str::<SeedGroups>(text)
+            .map(Vec::from)
+            .map_err(|e| error(e.line(), 0, e.to_string())),
+        SeedFormat::Toml => toml::from_str::<BTreeMap<String, Vec<String>>>(text)
+            .map(|groups| SeedGroups::Map(groups).into())
+            .map_err(|e| {
+                let offset = e.span().map(|span| span.start).unwrap_or(0);
+                let line = text[..offset].matches('\n').count() + 1;
+                error(line, offset, e.message().to_string())
+            }),
+        SeedFormat::Text => {
+            let mut groups: Vec<SubspaceSeeds> = Vec::new();
+            let mut offset = 0;
+            for (i, raw) in text.lines().enumerate() {
+                let line = raw.split('#').next().unwrap_or_default().trim();
+                if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                    groups.push(SubspaceSeeds::new(name.trim().to_string(), Vec::new()));
+                } else if !line.is_empty() {
+                    match groups.last_mut() {
+                        Some(group) => group.seeds.push(line.to_string()),
+                        None => {
+                            return Err(error(
+                                i + 1,
+                                offset,
+                                "seed outside of a `[group]`".to_string(),
+                            ))
+                        }
+                    }
+                }
+                offset += raw.len() + 1;
+            }
+            Ok(groups)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names_and_seeds(groups: &[SubspaceSeeds]) -> Vec<(String, Vec<String>)> {
+        groups
+            .iter()
+            .map(|group| (group.name.clone(), group.seeds.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_formats_agree() {
+        let json = parse_seeds(
+            r#"{"female": ["she"], "male": ["he", "police officer"]}"#,
+            SeedFormat::Json,
+            "seeds.json",
+        )
+        .unwrap();
+        let json_list = parse_seeds(
+            r#"[{"name": "female", "seeds": ["she"]},
+                {"name": "male", "seeds": ["he", "police officer"]}]"#,
+            SeedFormat::Json,
+            "seeds.json",
+        )
+        .unwrap();
+        let toml = parse_seeds(
+            "female = [\"she\"]\nmale = [\"he\", \"police officer\"]\n",
+            SeedFormat::Toml,
+            "seeds.toml",
+        )
+        .unwrap();
+        let text = parse_seeds(
+            "# gender\n[female]\nshe\n\n[male]\nhe\npolice officer # a phrase\n",
+            SeedFormat::Text,
+            "seeds.txt",
+        )
+        .unwrap();
+        assert_eq!(names_and_seeds(&json), names_and_seeds(&json_list));
+        assert_eq!(names_and_seeds(&json), names_and_seeds(&toml));
+        assert_eq!(names_and_seeds(&json), names_and_seeds(&text));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = parse_seeds("# gender\nhe\n", SeedFormat::Text, "seeds.txt").unwrap_err();
+        assert!(matches!(error, ReadError::Parse { line: 2, .. }));
+        let error = parse_seeds(
+            "male = [\"he\"]\nfemale = 3\n",
+            SeedFormat::Toml,
+            "seeds.toml",
+        )
+        .unwrap_err();
+        assert!(matches!(error, ReadError::Parse { line: 2, .. }));
+        assert_eq!(SeedFormat::detect("lexicon/gender.toml"), SeedFormat::Toml);
+    }
+}
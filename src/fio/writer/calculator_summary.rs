@@ -1,18 +1,88 @@
 use crate::fio::writer::WriterOperator;
+use crate::util::constant;
 use crate::Calculator;
 use std::fs::OpenOptions;
 use std::io::Write;
 
 impl WriterOperator for Calculator {
-    fn write(&self, path: &str, _if_show: bool) {
+    fn write(&self, path: &str, _if_show: bool) -> std::io::Result<()> {
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
-            .open(format!("{}/calculator_summary.txt", path))
-            .unwrap();
+            .truncate(true)
+            .open(format!("{}/calculator_summary.txt", path))?;
 
         // todo: format the output
-        file.write_all("This is the summary of the calculator\n".as_bytes())
-            .unwrap();
+        file.write_all("This is the summary of the calculator\n".as_bytes())?;
+        writeln!(file, "Center: {}", self.center_estimator)?;
+        writeln!(file, "Metric: {}", self.metric)?;
+
+        // the PCA the tokens were projected with, to project new tokens the same way
+        if let Some(pca) = &self.pca {
+            pca.to_file(&format!(
+                "{}/calculator_summary{}",
+                path,
+                constant::PCA_EXTENSION
+            ))
+            .map_err(|error| std::io::Error::other(error.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analyizer::metric::Scorer;
+    use crate::embedding::models::Token;
+    use crate::space::seeds::SubspaceSeeds;
+    use crate::space::space_generator::Space;
+    use crate::space::SpaceGenerator;
+    use crate::util::pca::PCA;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_pca_saved_with_the_summary() {
+        let tokens: Vec<Token> = (0..6)
+            .map(|i| {
+                let x = i as f64;
+                Token::new(format!("w{}", i), i, 0, vec![x, 2.0 * x, (x * 0.7).sin()])
+            })
+            .collect();
+        let pca = PCA::fit_tokens(2, &tokens);
+        let group = |name: &str, tokens: &[Token]| {
+            let seeds = SubspaceSeeds::new(name.to_string(), vec![name.to_string()]);
+            Space::new(pca.transform_tokens(tokens), Some(seeds), None)
+        };
+        let calculator = Calculator::from_tokens(
+            "test".to_string(),
+            pca.transform_tokens(&tokens[4..]),
+            vec![group("a", &tokens[..2]), group("b", &tokens[2..4])],
+            &Scorer::default(),
+        )
+        .with_pca(Some(pca.clone()));
+
+        let dir = std::env::temp_dir().join(format!("wafflecone-summary-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_string_lossy().to_string();
+        calculator.save_summary(Some(&dir)).unwrap();
+
+        // as `load_pca` reads it back
+        let loaded = PCA::from_file(&format!(
+            "{}/calculator_summary{}",
+            dir,
+            constant::PCA_EXTENSION
+        ))
+        .unwrap();
+        let summary = std::fs::read_to_string(format!("{}/calculator_summary.txt", dir)).unwrap();
+        assert!(summary.contains("Center: mean"));
+        assert!(summary.contains("Metric: cosine"));
+        assert_eq!(loaded.get_n_components(), 2);
+        let projected = loaded.project(&tokens[5].embedding);
+        for (loaded, expected) in projected.iter().zip(pca.project(&tokens[5].embedding)) {
+            assert_abs_diff_eq!(*loaded, expected, epsilon = 1e-12);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -2,5 +2,5 @@ pub mod calculator_summary;
 pub mod subspace;
 
 pub trait WriterOperator {
-    fn write(&self, path: &str, if_show: bool);
+    fn write(&self, path: &str, if_show: bool) -> std::io::Result<()>;
 }
@@ -3,15 +3,17 @@ use crate::space::space_generator::Space;
 use crate::util::constant;
 use crate::util::progress_bar::ProgressBar;
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 
 impl WriterOperator for Space {
-    fn write(&self, path: &str, if_show: bool) {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(path)
-            .unwrap();
+    fn write(&self, path: &str, if_show: bool) -> std::io::Result<()> {
+        let mut file = BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?,
+        );
 
         let mut progress_bar = ProgressBar::new(
             self.tokens.len() as u64,
@@ -22,19 +24,22 @@ impl WriterOperator for Space {
         for token in &self.tokens {
             let mut line = String::new();
             line.push_str(&token.word);
-            line.push_str(" ");
+            line.push(' ');
             line.push_str(&token.line_num.to_string());
-            line.push_str(" ");
+            line.push(' ');
             line.push_str(&token.position.to_string());
-            line.push_str(" ");
+            line.push(' ');
+            line.push_str(&token.source_id.to_string());
+            line.push(' ');
             for value in &token.embedding {
                 line.push_str(&value.to_string());
-                line.push_str(",");
+                line.push(',');
             }
-            line.push_str("\n");
-            file.write_all(line.as_bytes()).unwrap();
+            line.push('\n');
+            file.write_all(line.as_bytes())?;
             progress_bar.inc(1);
         }
         progress_bar.finish();
+        file.flush()
     }
 }
@@ -7,16 +7,35 @@ mod space;
 mod util;
 mod web;
 
-use embedding::models::Token;
+use embedding::array::{extract_matrix, matrix_to_lines};
+use embedding::layer::{profiled_layers, select_line_layers, LayerSelection};
+use embedding::models::{Line, Token};
+use embedding::normalizer::{Normalizer, TokenizerPreset};
+use embedding::pooling::{pool_line, Pooling};
 
-use fio::reader::conceptx::ConceptXReader;
-use fio::reader::Reader;
+use fio::reader::error::{DimensionError, ParseError, ReadError};
+use fio::reader::{
+    check_rereadable, read_sources, stream_sources, InputPaths, ReadOptions, ReaderType,
+};
+use fio::seeds::{load_seeds, SeedFormat};
 
 use crate::analyizer::calculator::Calculator;
-use crate::analyizer::SpaceCalculator;
+use crate::analyizer::metric::{Metric, Scorer};
+use crate::analyizer::profile::LayerProfile;
+use crate::space::center::CenterEstimator;
+use crate::space::coverage::{CoverageError, SeedCoverage, SeedCoverageError};
+use crate::space::dataset::Dataset;
+use crate::space::debias::DebiasResult;
+use crate::space::direction::DefinitionalPairs;
+use crate::space::inlp::InlpResult;
+use crate::space::lexicon::{builtin_seeds, list_lexicons as lexicon_names};
+use crate::space::matching::MatchOptions;
+use crate::space::preprocess::{PreprocessReport, Preprocessing};
 use crate::space::seeds::SubspaceSeeds;
 use crate::space::space_generator::Space;
+use crate::space::stream::{neutral_tokens, scan_seeds};
 use crate::space::SpaceGenerator;
+use crate::util::pca::PCA;
 use crate::util::Message;
 
 #[pyfunction]
@@ -31,51 +50,521 @@ fn new_subspace_seeds(name: String, seeds: Vec<String>) -> SubspaceSeeds {
 }
 
 #[pyfunction]
-fn calculator(
+fn load_subspace_seeds(
     path: &str,
+    format: Option<SeedFormat>, // "json", "toml" or "text", guessed from the extension by default
+) -> PyResult<Vec<SubspaceSeeds>> {
+    Ok(load_seeds(path, format)?)
+}
+
+#[pyfunction]
+fn builtin_subspace_seeds(name: &str) -> PyResult<Vec<SubspaceSeeds>> {
+    builtin_seeds(name).ok_or_else(|| {
+        pyo3::exceptions::PyValueError::new_err(format!(
+            "Unknown lexicon `{}`, available: {}",
+            name,
+            lexicon_names().join(", ")
+        ))
+    })
+}
+
+#[pyfunction]
+fn list_lexicons() -> Vec<String> {
+    lexicon_names()
+}
+
+#[pyfunction]
+fn new_normalizer(
+    preset: Option<TokenizerPreset>, // "legacy", "wordpiece", "bpe", "sentencepiece" or "none"
+    rules: Option<Vec<(String, String)>>, // literal (pattern, replacement) pairs
+    lowercase: Option<bool>,
+    nfkc: Option<bool>,
+) -> Normalizer {
+    Normalizer::new(
+        preset.unwrap_or(TokenizerPreset::Legacy),
+        rules.unwrap_or_default(),
+        lowercase.unwrap_or(false),
+        nfkc.unwrap_or(false),
+    )
+}
+
+#[pyfunction]
+fn new_match_options(
+    case_fold: Option<bool>,     // "He" matches the seed "he"
+    nfkc: Option<bool>,          // Unicode NFKC normalization, e.g. full width letters
+    strip_accents: Option<bool>, // "fiancée" matches the seed "fiancee"
+) -> MatchOptions {
+    MatchOptions::new(
+        case_fold.unwrap_or(false),
+        nfkc.unwrap_or(false),
+        strip_accents.unwrap_or(false),
+    )
+}
+
+#[pyfunction]
+fn load_pca(path: &str) -> PyResult<PCA> {
+    Ok(PCA::from_file(path)?)
+}
+
+#[pyfunction]
+fn new_preprocessing(
+    center: Option<bool>,           // subtract the mean of the space
+    standardize: Option<bool>,      // divide each dimension by its standard deviation
+    remove_top: Option<usize>,      // dominant principal components removed, "all-but-the-top"
+    rogue_dimensions: Option<bool>, // scale down the dimensions dominating the cosine similarity
+) -> Preprocessing {
+    Preprocessing::new(
+        center.unwrap_or(false),
+        standardize.unwrap_or(false),
+        remove_top.unwrap_or(0),
+        rogue_dimensions.unwrap_or(false),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+fn calculator(
+    path: InputPaths, // a file, a directory, a glob pattern or a list of them
+    subspace_seeds: Vec<SubspaceSeeds>,
+    exclude_words: Option<Vec<String>>, // words to exclude from random tokens
+    user_friendly: Option<bool>,
+    pca_dimension: Option<usize>,
+    model_name: Option<String>,
+    layer: Option<LayerSelection>, // layer index, list of layers, "last", "concat" or "mean"
+    streaming: Option<bool>,       // read the file lazily, in two passes, instead of loading it
+    skip_bad_lines: Option<bool>,  // skip and report malformed lines instead of raising
+    reader: Option<ReaderType>,    // "conceptx", "numpy", "word2vec", "word2vec_binary" or "glove"
+    vocab_path: Option<String>, // token list of a numpy array or a directory of them, `<path>.tsv` by default
+    normalizer: Option<Normalizer>, // subword marker handling, see `new_normalizer`
+    subword_pooling: Option<Pooling>, // merge subword pieces into words: "mean", "first", "last" or "max"
+    matching: Option<MatchOptions>, // how seeds and exclusions match tokens, see `new_match_options`
+    threads: Option<usize>,         // threads parsing the file, all cores by default
+    cache: Option<bool>,            // reuse a binary cache of the parsed file, written next to it
+    min_coverage: Option<f64>,      // share of the seeds of each subspace that must occur, e.g. 0.5
+    definitional_pairs: Option<Vec<(String, String)>>, // e.g. [("he", "she")], for a bias direction
+    bias_dimension: Option<usize>,  // principal components of the pairs kept, 1 by default
+    center: Option<CenterEstimator>, // group centers: "mean", "balanced", "trimmed", "geometric_median" or "medoid"
+    metric: Option<Metric>, // token to group center: "cosine", "euclidean", "mahalanobis", "dot" or "angular"
+    preprocessing: Option<Preprocessing>, // anisotropy correction of the space, see `new_preprocessing`
+    pca_model: Option<PCA>, // a fitted PCA, see `load_pca` and `Dataset.fit_pca`, instead of pca_dimension
+) -> PyResult<Calculator> {
+    let layer = layer.unwrap_or(LayerSelection::Last);
+    let options = ReadOptions {
+        user_friendly: user_friendly.unwrap_or(false),
+        skip_bad_lines: skip_bad_lines.unwrap_or(false),
+        vocab_path,
+        normalizer: normalizer.unwrap_or_default(),
+        threads,
+        cache: cache.unwrap_or(false),
+    };
+    let paths = path.resolve()?;
+    let path = path.to_string();
+    let reduced_dimension = reduced_dimension(pca_dimension, pca_model.as_ref())?;
+    Message::calculator_info(model_name.clone(), &path, reduced_dimension, Some(&layer));
+    let center = center.unwrap_or_default();
+    let metric = metric.unwrap_or_default();
+    Message::scoring_info(&center, &metric);
+    if streaming.unwrap_or(false) {
+        if pca_dimension.is_some() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Fitting a PCA needs all tokens in memory and cannot be used with streaming, a fitted pca_model can",
+            ));
+        }
+        if definitional_pairs.is_some() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "The bias direction needs all tokens in memory and cannot be used with streaming",
+            ));
+        }
+        if preprocessing.is_some() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Preprocessing needs all tokens in memory and cannot be used with streaming",
+            ));
+        }
+        if metric == Metric::Mahalanobis {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "The Mahalanobis distance needs all tokens in memory and cannot be used with streaming",
+            ));
+        }
+        return stream_calculator(
+            &paths,
+            subspace_seeds,
+            exclude_words.unwrap_or_default(),
+            &layer,
+            subword_pooling,
+            &matching.unwrap_or_default(),
+            min_coverage,
+            center,
+            metric,
+            pca_model.as_ref(),
+            reader,
+            &options,
+            model_name.unwrap_or(path),
+        );
+    }
+    let data = load_words(&paths, reader, &options, &layer, subword_pooling)?;
+
+    let dataset = new_dataset(
+        model_name.unwrap_or(path),
+        data,
+        pca_dimension,
+        pca_model.as_ref(),
+    )?
+    .with_preprocessing(&preprocessing.unwrap_or_default());
+    let definitional_pairs =
+        definitional_pairs.map(|pairs| DefinitionalPairs::new(pairs, bias_dimension.unwrap_or(1)));
+    Ok(dataset.build_calculator(
+        subspace_seeds,
+        exclude_words.unwrap_or_default(),
+        &matching.unwrap_or_default(),
+        min_coverage,
+        definitional_pairs.as_ref(),
+        center,
+        metric,
+    )?)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+fn dataset(
+    path: InputPaths, // a file, a directory, a glob pattern or a list of them
+    user_friendly: Option<bool>,
+    pca_dimension: Option<usize>,
+    model_name: Option<String>,
+    layer: Option<LayerSelection>, // layer index, list of layers, "last", "concat" or "mean"
+    skip_bad_lines: Option<bool>,  // skip and report malformed lines instead of raising
+    reader: Option<ReaderType>,    // "conceptx", "numpy", "word2vec", "word2vec_binary" or "glove"
+    vocab_path: Option<String>, // token list of a numpy array or a directory of them, `<path>.tsv` by default
+    normalizer: Option<Normalizer>, // subword marker handling, see `new_normalizer`
+    subword_pooling: Option<Pooling>, // merge subword pieces into words: "mean", "first", "last" or "max"
+    threads: Option<usize>,           // threads parsing the file, all cores by default
+    cache: Option<bool>,              // reuse a binary cache of the parsed file, written next to it
+    preprocessing: Option<Preprocessing>, // anisotropy correction of the space, see `new_preprocessing`
+    pca_model: Option<PCA>, // a fitted PCA, see `load_pca` and `Dataset.fit_pca`, instead of pca_dimension
+) -> PyResult<Dataset> {
+    let layer = layer.unwrap_or(LayerSelection::Last);
+    let options = ReadOptions {
+        user_friendly: user_friendly.unwrap_or(false),
+        skip_bad_lines: skip_bad_lines.unwrap_or(false),
+        vocab_path,
+        normalizer: normalizer.unwrap_or_default(),
+        threads,
+        cache: cache.unwrap_or(false),
+    };
+    let paths = path.resolve()?;
+    let path = path.to_string();
+    let reduced_dimension = reduced_dimension(pca_dimension, pca_model.as_ref())?;
+    Message::calculator_info(model_name.clone(), &path, reduced_dimension, Some(&layer));
+    let data = load_words(&paths, reader, &options, &layer, subword_pooling)?;
+
+    Ok(new_dataset(
+        model_name.unwrap_or(path),
+        data,
+        pca_dimension,
+        pca_model.as_ref(),
+    )?
+    .with_preprocessing(&preprocessing.unwrap_or_default())
+    .with_sources(paths))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+fn calculator_from_embeddings(
+    embeddings: &PyAny, // 2-D (tokens, dim) or 3-D (tokens, layers, dim) array
+    words: Vec<String>,
+    subspace_seeds: Vec<SubspaceSeeds>,
+    line_nums: Option<Vec<usize>>, // every token is its own line by default
+    positions: Option<Vec<usize>>,
+    exclude_words: Option<Vec<String>>, // words to exclude from random tokens
+    pca_dimension: Option<usize>,
+    model_name: Option<String>,
+    layer: Option<LayerSelection>, // layer index, list of layers, "last", "concat" or "mean"
+    normalizer: Option<Normalizer>, // subword marker handling, see `new_normalizer`
+    subword_pooling: Option<Pooling>, // merge subword pieces into words: "mean", "first", "last" or "max"
+    matching: Option<MatchOptions>, // how seeds and exclusions match tokens, see `new_match_options`
+    min_coverage: Option<f64>,      // share of the seeds of each subspace that must occur, e.g. 0.5
+    definitional_pairs: Option<Vec<(String, String)>>, // e.g. [("he", "she")], for a bias direction
+    bias_dimension: Option<usize>,  // principal components of the pairs kept, 1 by default
+    center: Option<CenterEstimator>, // group centers: "mean", "balanced", "trimmed", "geometric_median" or "medoid"
+    metric: Option<Metric>, // token to group center: "cosine", "euclidean", "mahalanobis", "dot" or "angular"
+    preprocessing: Option<Preprocessing>, // anisotropy correction of the space, see `new_preprocessing`
+    pca_model: Option<PCA>, // a fitted PCA, see `load_pca` and `Dataset.fit_pca`, instead of pca_dimension
+) -> PyResult<Calculator> {
+    let model_name = model_name.unwrap_or_else(|| "in-memory embeddings".to_string());
+    let layer = layer.unwrap_or(LayerSelection::Last);
+    let reduced_dimension = reduced_dimension(pca_dimension, pca_model.as_ref())?;
+    Message::calculator_info(
+        Some(model_name.clone()),
+        "memory",
+        reduced_dimension,
+        Some(&layer),
+    );
+    let center = center.unwrap_or_default();
+    let metric = metric.unwrap_or_default();
+    Message::scoring_info(&center, &metric);
+
+    let matrix = extract_matrix(embeddings)?;
+    let mut data = matrix_to_lines(&matrix, words, line_nums, positions)?;
+    let normalizer = normalizer.unwrap_or_default();
+    for line in &mut data {
+        normalizer.normalize_line(line);
+    }
+    let data = select_words(&data, &layer, subword_pooling)?;
+    let num_of_tokens: usize = data.iter().map(|line| line.tokens.len()).sum();
+    println!("Total number of tokens: {}", num_of_tokens);
+
+    let dataset = new_dataset(model_name, data, pca_dimension, pca_model.as_ref())?
+        .with_preprocessing(&preprocessing.unwrap_or_default());
+    let definitional_pairs =
+        definitional_pairs.map(|pairs| DefinitionalPairs::new(pairs, bias_dimension.unwrap_or(1)));
+    Ok(dataset.build_calculator(
+        subspace_seeds,
+        exclude_words.unwrap_or_default(),
+        &matching.unwrap_or_default(),
+        min_coverage,
+        definitional_pairs.as_ref(),
+        center,
+        metric,
+    )?)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+fn layer_profile(
+    path: InputPaths, // a file, a directory, a glob pattern or a list of them
     subspace_seeds: Vec<SubspaceSeeds>,
     exclude_words: Option<Vec<String>>, // words to exclude from random tokens
     user_friendly: Option<bool>,
     pca_dimension: Option<usize>,
     model_name: Option<String>,
-) -> Calculator {
-    Message::calculator_info(model_name.clone(), path, pca_dimension);
-    let data = ConceptXReader::new().read(path, user_friendly.unwrap_or(false));
+    top_k: Option<usize>,         // number of most biased tokens kept per layer
+    skip_bad_lines: Option<bool>, // skip and report malformed lines instead of raising
+    reader: Option<ReaderType>,   // "conceptx", "numpy", "word2vec", "word2vec_binary" or "glove"
+    vocab_path: Option<String>, // token list of a numpy array or a directory of them, `<path>.tsv` by default
+    normalizer: Option<Normalizer>, // subword marker handling, see `new_normalizer`
+    subword_pooling: Option<Pooling>, // merge subword pieces into words: "mean", "first", "last" or "max"
+    matching: Option<MatchOptions>, // how seeds and exclusions match tokens, see `new_match_options`
+    threads: Option<usize>,         // threads parsing the file, all cores by default
+    cache: Option<bool>,            // reuse a binary cache of the parsed file, written next to it
+    min_coverage: Option<f64>,      // share of the seeds of each subspace that must occur, e.g. 0.5
+    center: Option<CenterEstimator>, // group centers: "mean", "balanced", "trimmed", "geometric_median" or "medoid"
+    metric: Option<Metric>, // token to group center: "cosine", "euclidean", "mahalanobis", "dot" or "angular"
+    preprocessing: Option<Preprocessing>, // anisotropy correction of the space, see `new_preprocessing`
+    pca_model: Option<PCA>, // a fitted PCA, see `load_pca` and `Dataset.fit_pca`, instead of pca_dimension
+    layers: Option<Vec<usize>>, // layer indices to profile, all layers of the file by default
+) -> PyResult<LayerProfile> {
+    let paths = path.resolve()?;
+    let path = path.to_string();
+    let model_name = model_name.unwrap_or_else(|| path.clone());
+    let reduced_dimension = reduced_dimension(pca_dimension, pca_model.as_ref())?;
+    Message::calculator_info(Some(model_name.clone()), &path, reduced_dimension, None);
+    let center = center.unwrap_or_default();
+    let metric = metric.unwrap_or_default();
+    Message::scoring_info(&center, &metric);
+    // parse once, every layer is selected from the same lines
+    let options = ReadOptions {
+        user_friendly: user_friendly.unwrap_or(false),
+        skip_bad_lines: skip_bad_lines.unwrap_or(false),
+        vocab_path,
+        normalizer: normalizer.unwrap_or_default(),
+        threads,
+        cache: cache.unwrap_or(false),
+    };
+    let data = read_sources(&paths, reader, &options)?;
+    let layers = profiled_layers(&data, layers.as_deref(), &path)?;
+    println!("Number of layers: {}", layers.len());
+
+    let matching = matching.unwrap_or_default();
+    let preprocessing = preprocessing.unwrap_or_default();
+    let mut profile = LayerProfile::new(model_name.clone());
+    for layer in layers {
+        let dataset = new_dataset(
+            model_name.clone(),
+            select_words(&data, &LayerSelection::Layer(layer), subword_pooling)?,
+            pca_dimension,
+            pca_model.as_ref(),
+        )?
+        .with_preprocessing(&preprocessing);
+        let calculator = dataset.build_calculator(
+            subspace_seeds.clone(),
+            exclude_words.clone().unwrap_or_default(),
+            &matching,
+            min_coverage,
+            None,
+            center,
+            metric,
+        )?;
+        println!("🧅 Layer {}: bias {:.4}", layer, calculator.get_bias());
+        profile.push(layer, &calculator, top_k.unwrap_or(10));
+    }
+    Ok(profile)
+}
+
+/// One token per word: the selected layer(s) of every piece, then the pieces pooled
+fn select_words(
+    lines: &[Line],
+    layer: &LayerSelection,
+    pooling: Option<Pooling>,
+) -> Result<Vec<Line>, ReadError> {
+    lines
+        .iter()
+        .map(|line| select_line_words(line, layer, pooling))
+        .collect()
+}
+
+fn select_line_words(
+    line: &Line,
+    layer: &LayerSelection,
+    pooling: Option<Pooling>,
+) -> Result<Line, ReadError> {
+    let line = select_line_layers(line, layer)?;
+    Ok(match pooling {
+        Some(pooling) => pool_line(&line, pooling),
+        None => line,
+    })
+}
+
+/// Read the files and reduce them to one token per word
+fn load_words(
+    paths: &[String],
+    reader: Option<ReaderType>,
+    options: &ReadOptions,
+    layer: &LayerSelection,
+    pooling: Option<Pooling>,
+) -> Result<Vec<Line>, ReadError> {
+    let data = select_words(&read_sources(paths, reader, options)?, layer, pooling)?;
 
     let mut num_of_tokens = 0;
     for line in &data {
         num_of_tokens += line.tokens.len();
     }
     println!("Total number of tokens: {}", num_of_tokens);
+    Ok(data)
+}
 
-    // Build the global space
-    let space = Space::new(data, None, pca_dimension);
+/// The dimension after the PCA, either fitted on the dataset or given
+fn reduced_dimension(
+    pca_dimension: Option<usize>,
+    pca_model: Option<&PCA>,
+) -> PyResult<Option<usize>> {
+    match pca_model {
+        Some(_) if pca_dimension.is_some() => Err(pyo3::exceptions::PyValueError::new_err(
+            "pca_dimension fits a new PCA and cannot be used with pca_model",
+        )),
+        Some(pca) => Ok(Some(pca.get_n_components())),
+        None => Ok(pca_dimension),
+    }
+}
+
+fn new_dataset(
+    model_name: String,
+    lines: Vec<Line>,
+    pca_dimension: Option<usize>,
+    pca_model: Option<&PCA>,
+) -> PyResult<Dataset> {
+    match pca_model {
+        Some(pca) => Dataset::with_pca_model(model_name, lines, pca),
+        None => Ok(Dataset::new(model_name, lines, pca_dimension)?),
+    }
+}
+
+/// Project the tokens of a streamed line with a fitted PCA
+fn project_line(line: Line, pca: Option<&PCA>, paths: &[String]) -> Result<Line, ReadError> {
+    let pca = match pca {
+        Some(pca) => pca,
+        None => return Ok(line),
+    };
+    let mut tokens = Vec::with_capacity(line.tokens.len());
+    for token in &line.tokens {
+        if token.embedding.len() != pca.dimension() {
+            return Err(ReadError::Dimension {
+                path: paths.get(token.source_id).cloned().unwrap_or_default(),
+                line: line.line_num,
+                expected: pca.dimension(),
+                found: token.embedding.len(),
+            });
+        }
+        tokens.push(token.with_embedding(pca.project(&token.embedding)));
+    }
+    Ok(Line { tokens, ..line })
+}
+
+/// Same as `Dataset::build_calculator`, but with two passes over the files instead of loading them:
+/// the first one finds the seed tokens (the group centers), the second one scores the
+/// neutral tokens as they are read. Files that cannot be read twice, such as pipes, are refused.
+#[allow(clippy::too_many_arguments)]
+fn stream_calculator(
+    paths: &[String],
+    subspace_seeds: Vec<SubspaceSeeds>,
+    exclude_words: Vec<String>,
+    layer: &LayerSelection,
+    pooling: Option<Pooling>,
+    matching: &MatchOptions,
+    min_coverage: Option<f64>,
+    center: CenterEstimator,
+    metric: Metric,
+    pca: Option<&PCA>,
+    reader: Option<ReaderType>,
+    options: &ReadOptions,
+    model_name: String,
+) -> PyResult<Calculator> {
+    check_rereadable(paths)?;
+    // the streams stop at the first error, which is raised once they are consumed
+    let mut error: Option<ReadError> = None;
+    let scan = scan_seeds(
+        stream_sources(paths, reader, options).map_while(|line| {
+            line.and_then(|line| select_line_words(&line, layer, pooling))
+                .and_then(|line| project_line(line, pca, paths))
+                .map_err(|e| error = Some(e))
+                .ok()
+        }),
+        &subspace_seeds,
+        matching,
+    );
+    if let Some(error) = error {
+        return Err(error.into());
+    }
+    println!("Total number of tokens: {}", scan.num_of_tokens);
+    let seed_coverage =
+        SeedCoverage::check(&subspace_seeds, &scan.seed_tokens, matching, min_coverage)?;
 
     // all words need to be excluded: exclude_words + subspace_seeds
-    let mut exclude_words = exclude_words.unwrap_or_default();
+    let mut exclude_words = exclude_words;
     for subspace_seed in &subspace_seeds {
         exclude_words.extend(subspace_seed.seeds.clone());
     }
-    let neutral_tokens = space.get_neutral_tokens(exclude_words);
-    let neutral_space = Space::new(neutral_tokens, None, pca_dimension);
 
-    // build subspaces with the tokens of interests. e.g., male or female
-    let mut sub_spaces: Vec<Space> = Vec::new();
+    let sub_spaces: Vec<Space> = scan
+        .seed_tokens
+        .into_iter()
+        .zip(subspace_seeds)
+        .map(|(tokens, subspace_seed)| {
+            Space::new(tokens, Some(subspace_seed), None).with_center(center, matching)
+        })
+        .collect();
 
-    for subspace_seed in subspace_seeds {
-        let sub_space = Space::new(space.find(&subspace_seed), Some(subspace_seed), None);
-        sub_spaces.push(sub_space);
+    let mut error: Option<ReadError> = None;
+    let neutral = neutral_tokens(
+        stream_sources(paths, reader, options).map_while(|line| {
+            line.and_then(|line| select_line_words(&line, layer, pooling))
+                .and_then(|line| project_line(line, pca, paths))
+                .map_err(|e| error = Some(e))
+                .ok()
+        }),
+        exclude_words,
+        matching,
+    );
+    let calculator =
+        Calculator::from_tokens(model_name, neutral, sub_spaces, &Scorer::new(metric, &[]));
+    match error {
+        Some(error) => Err(error.into()),
+        None if calculator.similarity_per_token.is_empty() => {
+            Err(CoverageError::EmptyNeutralSpace.into())
+        }
+        None => Ok(calculator
+            .with_seed_coverage(seed_coverage)
+            .with_center_estimator(center)
+            .with_pca(pca.cloned())),
     }
-
-    // compute the bias of the random subspace
-    Calculator::new(
-        match model_name {
-            Some(name) => name,
-            None => path.to_string(),
-        },
-        neutral_space,
-        sub_spaces,
-    )
 }
 
 #[pyfunction]
@@ -85,11 +574,34 @@ fn visualize(port: Option<u16>) {
 }
 
 #[pymodule]
-fn wafflecone(_py: Python, m: &PyModule) -> PyResult<()> {
+fn wafflecone(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(version, m)?)?;
     m.add_function(wrap_pyfunction!(calculator, m)?)?;
+    m.add_function(wrap_pyfunction!(calculator_from_embeddings, m)?)?;
+    m.add_function(wrap_pyfunction!(dataset, m)?)?;
+    m.add_function(wrap_pyfunction!(layer_profile, m)?)?;
     m.add_function(wrap_pyfunction!(visualize, m)?)?;
     m.add_function(wrap_pyfunction!(new_subspace_seeds, m)?)?;
+    m.add_function(wrap_pyfunction!(load_subspace_seeds, m)?)?;
+    m.add_function(wrap_pyfunction!(builtin_subspace_seeds, m)?)?;
+    m.add_function(wrap_pyfunction!(list_lexicons, m)?)?;
+    m.add_function(wrap_pyfunction!(new_normalizer, m)?)?;
+    m.add_function(wrap_pyfunction!(new_match_options, m)?)?;
+    m.add_function(wrap_pyfunction!(new_preprocessing, m)?)?;
+    m.add_function(wrap_pyfunction!(load_pca, m)?)?;
     m.add_class::<SubspaceSeeds>()?;
+    m.add_class::<LayerProfile>()?;
+    m.add_class::<Dataset>()?;
+    m.add_class::<Normalizer>()?;
+    m.add_class::<MatchOptions>()?;
+    m.add_class::<SeedCoverage>()?;
+    m.add_class::<DebiasResult>()?;
+    m.add_class::<InlpResult>()?;
+    m.add_class::<Preprocessing>()?;
+    m.add_class::<PreprocessReport>()?;
+    m.add_class::<PCA>()?;
+    m.add("ParseError", py.get_type::<ParseError>())?;
+    m.add("DimensionError", py.get_type::<DimensionError>())?;
+    m.add("SeedCoverageError", py.get_type::<SeedCoverageError>())?;
     Ok(())
 }
@@ -0,0 +1,217 @@
+use crate::embedding::models::Token;
+use crate::space::matching::MatchOptions;
+use crate::space::vector::mean;
+use crate::util::constant;
+use pyo3::{FromPyObject, PyAny, PyErr, PyResult};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// How the center of a group is estimated from its tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CenterEstimator {
+    /// The mean of all the tokens, a frequent word weighs as much as its occurrences
+    #[default]
+    Mean,
+    /// The mean of the mean of each word, every word weighs the same. The words are
+    /// told apart as the seeds match them, e.g. "He" and "he" are one word with case folding
+    Balanced,
+    /// The mean of each dimension without the lowest and highest `TRIM_FRACTION`
+    Trimmed,
+    /// The point with the smallest sum of distances to the tokens
+    GeometricMedian,
+    /// The token with the smallest sum of distances to the other tokens, among at most
+    /// `MEDOID_SAMPLE` tokens evenly spread over the group
+    Medoid,
+}
+
+impl<'a> FromPyObject<'a> for CenterEstimator {
+    fn extract(obj: &'a PyAny) -> PyResult<Self> {
+        if let Ok(string) = obj.extract::<&str>() {
+            match string {
+                "mean" => Ok(CenterEstimator::Mean),
+                "balanced" => Ok(CenterEstimator::Balanced),
+                "trimmed" => Ok(CenterEstimator::Trimmed),
+                "geometric_median" => Ok(CenterEstimator::GeometricMedian),
+                "medoid" => Ok(CenterEstimator::Medoid),
+                _ => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Invalid enum variant: {}",
+                    string
+                ))),
+            }
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "Invalid type for enum conversion",
+            ))
+        }
+    }
+}
+
+impl fmt::Display for CenterEstimator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CenterEstimator::Mean => write!(f, "mean"),
+            CenterEstimator::Balanced => write!(f, "balanced"),
+            CenterEstimator::Trimmed => write!(f, "trimmed"),
+            CenterEstimator::GeometricMedian => write!(f, "geometric_median"),
+            CenterEstimator::Medoid => write!(f, "medoid"),
+        }
+    }
+}
+
+impl CenterEstimator {
+    pub fn center(&self, tokens: &[Token], matching: &MatchOptions) -> Vec<f64> {
+        let embeddings: Vec<&[f64]> = tokens.iter().map(|token| &token.embedding[..]).collect();
+        match self {
+            CenterEstimator::Mean => mean(&embeddings),
+            CenterEstimator::Balanced => {
+                // ordered by word, so the sum of the word means is the same on every run
+                let mut words: BTreeMap<String, Vec<&[f64]>> = BTreeMap::new();
+                for token in tokens {
+                    words
+                        .entry(matching.key(&token.word))
+                        .or_default()
+                        .push(&token.embedding);
+                }
+                let word_means: Vec<Vec<f64>> = words.values().map(|word| mean(word)).collect();
+                mean(&word_means)
+            }
+            CenterEstimator::Trimmed => trimmed_mean(&embeddings, constant::TRIM_FRACTION),
+            CenterEstimator::GeometricMedian => geometric_median(&embeddings),
+            CenterEstimator::Medoid => medoid(&sample(&embeddings, constant::MEDOID_SAMPLE)),
+        }
+    }
+}
+
+fn trimmed_mean(embeddings: &[&[f64]], fraction: f64) -> Vec<f64> {
+    let trimmed = (embeddings.len() as f64 * fraction).floor() as usize;
+    (0..embeddings[0].len())
+        .map(|i| {
+            let mut values: Vec<f64> = embeddings.iter().map(|embedding| embedding[i]).collect();
+            values.sort_by(f64::total_cmp);
+            let kept = &values[trimmed..values.len() - trimmed];
+            kept.iter().sum::<f64>() / kept.len() as f64
+        })
+        .collect()
+}
+
+/// Weiszfeld's algorithm, starting from the mean
+fn geometric_median(embeddings: &[&[f64]]) -> Vec<f64> {
+    let mut median = mean(embeddings);
+    for _ in 0..constant::GEOMETRIC_MEDIAN_ITERATIONS {
+        let mut numerator = vec![0.0; median.len()];
+        let mut denominator = 0.0;
+        for embedding in embeddings {
+            let distance = distance(embedding, &median);
+            if distance < constant::GEOMETRIC_MEDIAN_TOLERANCE {
+                // the median reached a token, whose weight would be infinite
+                return median;
+            }
+            for (n, x) in numerator.iter_mut().zip(embedding.iter()) {
+                *n += x / distance;
+            }
+            denominator += 1.0 / distance;
+        }
+        let next: Vec<f64> = numerator.iter().map(|n| n / denominator).collect();
+        let step = distance(&next, &median);
+        median = next;
+        if step < constant::GEOMETRIC_MEDIAN_TOLERANCE {
+            break;
+        }
+    }
+    median
+}
+
+fn medoid(embeddings: &[&[f64]]) -> Vec<f64> {
+    let total_distance = |embedding: &[f64]| -> f64 {
+        embeddings
+            .iter()
+            .map(|other| distance(embedding, other))
+            .sum()
+    };
+    embeddings
+        .iter()
+        .map(|embedding| (total_distance(embedding), embedding))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, embedding)| embedding.to_vec())
+        .unwrap_or_default()
+}
+
+/// At most `size` embeddings, taken at even steps so the sample is the same every time
+fn sample<'a>(embeddings: &[&'a [f64]], size: usize) -> Vec<&'a [f64]> {
+    if embeddings.len() <= size {
+        return embeddings.to_vec();
+    }
+    (0..size)
+        .map(|i| embeddings[i * embeddings.len() / size])
+        .collect()
+}
+
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn tokens() -> Vec<Token> {
+        // "he" is frequent, "him" is rare and far away
+        let mut tokens: Vec<Token> = (0..9)
+            .map(|i| Token::new("he".to_string(), i, 0, vec![0.0, 0.0]))
+            .collect();
+        tokens.push(Token::new("him".to_string(), 9, 0, vec![10.0, 0.0]));
+        tokens
+    }
+
+    #[test]
+    fn test_estimators() {
+        let tokens = tokens();
+        let matching = MatchOptions::default();
+        let center = |estimator: CenterEstimator| estimator.center(&tokens, &matching);
+        assert_eq!(center(CenterEstimator::Mean), vec![1.0, 0.0]);
+        assert_eq!(center(CenterEstimator::Balanced), vec![5.0, 0.0]);
+        assert_eq!(center(CenterEstimator::Trimmed), vec![0.0, 0.0]);
+        assert_eq!(center(CenterEstimator::Medoid), vec![0.0, 0.0]);
+        let median = center(CenterEstimator::GeometricMedian);
+        assert_abs_diff_eq!(median[0], 0.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_balanced_words_follow_the_matching() {
+        let mut tokens = tokens();
+        tokens.push(Token::new("He".to_string(), 10, 0, vec![0.0, 3.0]));
+        let case_fold = MatchOptions::new(true, false, false);
+        // "He" is one more "he" token, not a third word
+        assert_eq!(
+            CenterEstimator::Balanced.center(&tokens, &case_fold),
+            vec![5.0, 0.15]
+        );
+        assert_eq!(
+            CenterEstimator::Balanced.center(&tokens, &MatchOptions::default()),
+            vec![10.0 / 3.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn test_medoid_sample() {
+        let embeddings: Vec<Vec<f64>> = (0..10 * constant::MEDOID_SAMPLE)
+            .map(|i| vec![i as f64])
+            .collect();
+        let embeddings: Vec<&[f64]> = embeddings.iter().map(|e| &e[..]).collect();
+        let sampled = sample(&embeddings, constant::MEDOID_SAMPLE);
+        assert_eq!(sampled.len(), constant::MEDOID_SAMPLE);
+        assert_eq!(sampled[1], &[10.0]);
+        let tokens: Vec<Token> = embeddings
+            .iter()
+            .enumerate()
+            .map(|(i, e)| Token::new("w".to_string(), i, 0, e.to_vec()))
+            .collect();
+        let medoid = CenterEstimator::Medoid.center(&tokens, &MatchOptions::default());
+        assert_abs_diff_eq!(medoid[0], 5000.0, epsilon = 10.0);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::embedding::models::Token;
+use crate::space::matching::MatchOptions;
+use crate::space::seeds::SubspaceSeeds;
+use crate::util::Message;
+use pyo3::exceptions::PyValueError;
+use pyo3::{create_exception, pyclass, pymethods, PyErr};
+use std::collections::HashMap;
+use std::fmt;
+
+create_exception!(wafflecone, SeedCoverageError, PyValueError);
+
+#[derive(Debug)]
+pub enum CoverageError {
+    /// No token at all, e.g. an empty file or every line skipped
+    EmptySpace,
+    /// None of the seeds of the subspace occurs in the data
+    EmptySubspace { name: String },
+    /// Fewer seeds than `min_coverage` occur in the data
+    LowCoverage {
+        name: String,
+        coverage: f64,
+        min_coverage: f64,
+    },
+    /// Every token is a seed or an excluded word
+    EmptyNeutralSpace,
+    /// Fewer definitional pairs occur in the data than the dimension of the bias subspace
+    DefinitionalPairs { found: usize, dimension: usize },
+}
+
+impl fmt::Display for CoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoverageError::EmptySpace => write!(
+                f,
+                "the space is empty: no token was read, the input is empty or every line was skipped"
+            ),
+            CoverageError::EmptySubspace { name } => {
+                write!(
+                    f,
+                    "subspace `{}`: none of the seeds occurs in the data",
+                    name
+                )
+            }
+            CoverageError::LowCoverage {
+                name,
+                coverage,
+                min_coverage,
+            } => write!(
+                f,
+                "subspace `{}`: {:.1}% of the seeds occur in the data, below the minimum of {:.1}%",
+                name,
+                coverage * 100.0,
+                min_coverage * 100.0
+            ),
+            CoverageError::EmptyNeutralSpace => {
+                write!(
+                    f,
+                    "no token is left once the seeds and excluded words are removed"
+                )
+            }
+            CoverageError::DefinitionalPairs { found, dimension } => write!(
+                f,
+                "{} definitional pair(s) occur in the data, a bias subspace of dimension {} needs at least {}",
+                found, dimension, dimension.max(&1)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CoverageError {}
+
+impl From<CoverageError> for PyErr {
+    fn from(error: CoverageError) -> PyErr {
+        match error {
+            CoverageError::EmptySpace => PyValueError::new_err(error.to_string()),
+            _ => SeedCoverageError::new_err(error.to_string()),
+        }
+    }
+}
+
+/// How many times each seed of a subspace occurs in the data
+#[derive(Debug, Clone)]
+pub struct SubspaceCoverage {
+    pub(crate) name: String,
+    /// In the order of the seeds
+    pub(crate) counts: Vec<(String, usize)>,
+}
+
+impl SubspaceCoverage {
+    /// Count the found tokens per seed, compared with the same key they were matched with
+    pub fn new(subspace_seed: &SubspaceSeeds, found: &[Token], matching: &MatchOptions) -> Self {
+        // a phrase is found as one token whose word is its pieces joined by a space
+        let key = |word: &str| {
+            matching
+                .key(word)
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+        };
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+        for token in found {
+            *occurrences.entry(key(&token.word)).or_insert(0) += 1;
+        }
+        SubspaceCoverage {
+            name: subspace_seed.name.clone(),
+            counts: subspace_seed
+                .seeds
+                .iter()
+                .map(|seed| {
+                    let count = occurrences.get(&key(seed)).copied().unwrap_or(0);
+                    (seed.clone(), count)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn missing(&self) -> Vec<String> {
+        self.counts
+            .iter()
+            .filter(|(_, count)| *count == 0)
+            .map(|(seed, _)| seed.clone())
+            .collect()
+    }
+
+    /// The share of seeds found at least once
+    pub fn coverage(&self) -> f64 {
+        if self.counts.is_empty() {
+            return 0.0;
+        }
+        let found = self.counts.iter().filter(|(_, count)| *count > 0).count();
+        found as f64 / self.counts.len() as f64
+    }
+
+    #[allow(dead_code)]
+    pub fn num_of_tokens(&self) -> usize {
+        self.counts.iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// The seed coverage of every subspace of a calculator
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct SeedCoverage {
+    pub(crate) subspaces: Vec<SubspaceCoverage>,
+}
+
+impl SeedCoverage {
+    /// Report the coverage of each subspace, failing when one is empty or below `min_coverage`
+    pub fn check(
+        subspace_seeds: &[SubspaceSeeds],
+        found: &[Vec<Token>],
+        matching: &MatchOptions,
+        min_coverage: Option<f64>,
+    ) -> Result<Self, CoverageError> {
+        let subspaces: Vec<SubspaceCoverage> = subspace_seeds
+            .iter()
+            .zip(found)
+            .map(|(subspace_seed, found)| SubspaceCoverage::new(subspace_seed, found, matching))
+            .collect();
+        for (subspace, found) in subspaces.iter().zip(found) {
+            Message::seed_coverage(subspace);
+            if found.is_empty() {
+                return Err(CoverageError::EmptySubspace {
+                    name: subspace.name.clone(),
+                });
+            }
+            if let Some(min_coverage) = min_coverage {
+                if subspace.coverage() < min_coverage {
+                    return Err(CoverageError::LowCoverage {
+                        name: subspace.name.clone(),
+                        coverage: subspace.coverage(),
+                        min_coverage,
+                    });
+                }
+            }
+        }
+        Ok(SeedCoverage { subspaces })
+    }
+}
+
+// Expose to Python
+#[pymethods]
+impl SeedCoverage {
+    /// The number of occurrences of each seed, per subspace
+    fn get_counts(&self) -> HashMap<String, HashMap<String, usize>> {
+        self.subspaces
+            .iter()
+            .map(|subspace| {
+                (
+                    subspace.name.clone(),
+                    subspace.counts.iter().cloned().collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// The seeds that never occur, per subspace
+    fn get_missing(&self) -> HashMap<String, Vec<String>> {
+        self.subspaces
+            .iter()
+            .map(|subspace| (subspace.name.clone(), subspace.missing()))
+            .collect()
+    }
+
+    /// The share of seeds found at least once, per subspace
+    fn get_coverage(&self) -> HashMap<String, f64> {
+        self.subspaces
+            .iter()
+            .map(|subspace| (subspace.name.clone(), subspace.coverage()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tokens(words: &[&str]) -> Vec<Token> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| Token::new(word.to_string(), i, 0, vec![i as f64]))
+            .collect()
+    }
+
+    fn seeds() -> Vec<SubspaceSeeds> {
+        vec![SubspaceSeeds::new(
+            "male".to_string(),
+            vec![
+                "he".to_string(),
+                "him".to_string(),
+                "police officer".to_string(),
+            ],
+        )]
+    }
+
+    #[test]
+    fn test_coverage() {
+        let found = vec![tokens(&["He", "he", "police officer"])];
+        let matching = MatchOptions::new(true, false, false);
+        let coverage = SeedCoverage::check(&seeds(), &found, &matching, None).unwrap();
+        let subspace = &coverage.subspaces[0];
+        assert_eq!(
+            subspace.counts,
+            vec![
+                ("he".to_string(), 2),
+                ("him".to_string(), 0),
+                ("police officer".to_string(), 1)
+            ]
+        );
+        assert_eq!(subspace.missing(), vec!["him".to_string()]);
+        assert_eq!(subspace.num_of_tokens(), 3);
+
+        assert!(matches!(
+            SeedCoverage::check(&seeds(), &found, &matching, Some(0.9)),
+            Err(CoverageError::LowCoverage { .. })
+        ));
+        assert!(matches!(
+            SeedCoverage::check(&seeds(), &[Vec::new()], &matching, None),
+            Err(CoverageError::EmptySubspace { .. })
+        ));
+    }
+}
@@ -0,0 +1,611 @@
+use crate::analyizer::calculator::Calculator;
+use crate::analyizer::metric::{Metric, Scorer};
+use crate::embedding::models::{Line, Token};
+use crate::fio::reader::error::DimensionError;
+use crate::fio::writer::WriterOperator;
+use crate::space::center::CenterEstimator;
+use crate::space::coverage::{CoverageError, SeedCoverage};
+use crate::space::debias::{hard_debias, normalize_space, DebiasResult};
+use crate::space::direction::{BiasDirection, DefinitionalPairs, DirectionalBias};
+use crate::space::inlp::{InlpResult, NullspaceProjection};
+use crate::space::matching::MatchOptions;
+use crate::space::preprocess::{PreprocessReport, Preprocessing};
+use crate::space::seeds::SubspaceSeeds;
+use crate::space::space_generator::Space;
+use crate::space::SpaceGenerator;
+use crate::util::constant;
+use crate::util::pca::PCA;
+use crate::util::Message;
+use pyo3::{pyclass, pymethods, PyResult};
+
+/// The parsed lines and their global space, loaded once to build many calculators
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct Dataset {
+    pub(crate) model_name: String,
+    /// The lines themselves are dropped once their tokens are in the space
+    pub(crate) num_lines: usize,
+    /// Already reduced to `pca_dimension`, the neutral and group spaces are taken from it
+    pub(crate) space: Space,
+    pub(crate) pca_dimension: Option<usize>,
+    /// The files read, indexed by the `source_id` of the tokens
+    pub(crate) sources: Vec<String>,
+    /// What the anisotropy correction did to the space, if any was asked
+    pub(crate) preprocess_report: Option<PreprocessReport>,
+}
+
+impl Dataset {
+    /// Fails when there is no token at all, e.g. an empty file or every line skipped
+    pub fn new(
+        model_name: String,
+        lines: Vec<Line>,
+        pca_dimension: Option<usize>,
+    ) -> Result<Self, CoverageError> {
+        if lines.iter().all(|line| line.tokens.is_empty()) {
+            return Err(CoverageError::EmptySpace);
+        }
+        let num_lines = lines.len();
+        let space = Space::new(lines, None, pca_dimension);
+        Ok(Dataset {
+            model_name,
+            num_lines,
+            space,
+            pca_dimension,
+            sources: Vec::new(),
+            preprocess_report: None,
+        })
+    }
+
+    /// Correct the anisotropy of the space, after the PCA
+    pub fn with_preprocessing(mut self, preprocessing: &Preprocessing) -> Self {
+        if !preprocessing.is_identity() {
+            let (space, report) = preprocessing.apply(&self.space);
+            self.space = space;
+            self.preprocess_report = Some(report);
+        }
+        self
+    }
+
+    /// Like `new`, but projected with a PCA fitted elsewhere, e.g. on another model or corpus
+    pub fn with_pca_model(model_name: String, lines: Vec<Line>, pca: &PCA) -> PyResult<Self> {
+        let dataset = Dataset::new(model_name, lines, None)?;
+        dataset.build_projected(pca)
+    }
+
+    /// The same dataset, projected with the PCA. The dataset must not be reduced already.
+    pub fn build_projected(&self, pca: &PCA) -> PyResult<Self> {
+        let dimension = self.space.tokens[0].embedding.len();
+        if dimension != pca.dimension() {
+            return Err(DimensionError::new_err(format!(
+                "the PCA was fitted on embeddings of dimension {}, the dataset has {}",
+                pca.dimension(),
+                dimension
+            )));
+        }
+        Ok(Dataset {
+            pca_dimension: Some(pca.get_n_components()),
+            ..self.with_space(self.space.clone().with_pca(pca))
+        })
+    }
+
+    /// The same dataset with another space, e.g. debiased
+    fn with_space(&self, space: Space) -> Self {
+        Dataset {
+            model_name: self.model_name.clone(),
+            num_lines: self.num_lines,
+            space,
+            pca_dimension: self.pca_dimension,
+            sources: self.sources.clone(),
+            preprocess_report: self.preprocess_report.clone(),
+        }
+    }
+
+    /// Fit a PCA on the tokens that are not excluded, e.g. on the neutral tokens only
+    pub fn build_pca(
+        &self,
+        n_components: usize,
+        exclude_words: Vec<String>,
+        matching: &MatchOptions,
+    ) -> Result<PCA, CoverageError> {
+        let tokens = self.space.get_neutral_tokens(exclude_words, matching);
+        if tokens.is_empty() {
+            return Err(CoverageError::EmptyNeutralSpace);
+        }
+        Ok(PCA::fit_tokens(n_components, &tokens))
+    }
+
+    pub fn with_sources(mut self, sources: Vec<String>) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    /// Build the neutral and group spaces for the seeds and compute the bias.
+    /// Fails when a subspace is empty or covers less than `min_coverage` of its seeds.
+    /// With definitional pairs, the neutral tokens are also projected on the bias direction.
+    #[allow(clippy::too_many_arguments)]
+    pub fn build_calculator(
+        &self,
+        subspace_seeds: Vec<SubspaceSeeds>,
+        exclude_words: Vec<String>,
+        matching: &MatchOptions,
+        min_coverage: Option<f64>,
+        definitional_pairs: Option<&DefinitionalPairs>,
+        center: CenterEstimator,
+        metric: Metric,
+    ) -> Result<Calculator, CoverageError> {
+        let found: Vec<Vec<Token>> = subspace_seeds
+            .iter()
+            .map(|subspace_seed| self.space.find(subspace_seed, matching))
+            .collect();
+        let seed_coverage = SeedCoverage::check(&subspace_seeds, &found, matching, min_coverage)?;
+
+        // all words need to be excluded: exclude_words + subspace_seeds
+        let mut exclude_words = exclude_words;
+        for subspace_seed in &subspace_seeds {
+            exclude_words.extend(subspace_seed.seeds.clone());
+        }
+        let direction = match definitional_pairs {
+            Some(definitional_pairs) => {
+                exclude_words.extend(definitional_pairs.words());
+                Some(BiasDirection::from_pairs(
+                    &self.space.tokens,
+                    definitional_pairs,
+                    matching,
+                )?)
+            }
+            None => None,
+        };
+        // the tokens are already projected, a second PCA would change their basis
+        let neutral_tokens = self.space.get_neutral_tokens(exclude_words, matching);
+        if neutral_tokens.is_empty() {
+            return Err(CoverageError::EmptyNeutralSpace);
+        }
+        let neutral_space = Space::new(neutral_tokens, None, None);
+
+        // build subspaces with the tokens of interests. e.g., male or female
+        let sub_spaces: Vec<Space> = found
+            .into_iter()
+            .zip(subspace_seeds)
+            .map(|(tokens, subspace_seed)| {
+                Space::new(tokens, Some(subspace_seed), None).with_center(center, matching)
+            })
+            .collect();
+
+        let directional_bias =
+            direction.map(|direction| DirectionalBias::new(direction, &neutral_space.tokens));
+
+        // compute the bias of the random subspace
+        let scorer = Scorer::new(metric, &self.space.tokens);
+        let calculator = Calculator::from_tokens(
+            self.model_name.clone(),
+            neutral_space.tokens,
+            sub_spaces,
+            &scorer,
+        )
+        .with_seed_coverage(seed_coverage)
+        .with_center_estimator(center)
+        .with_pca(self.space.pca.clone());
+        Ok(match directional_bias {
+            Some(directional_bias) => calculator.with_directional_bias(directional_bias),
+            None => calculator,
+        })
+    }
+}
+
+impl Dataset {
+    /// Hard debias the space along the direction of the definitional pairs, the seeds
+    /// keeping their bias, and compute the bias again on the result. Both biases are
+    /// measured on unit length embeddings, so the difference only comes from the debiasing.
+    #[allow(clippy::too_many_arguments)]
+    pub fn build_debiased(
+        &self,
+        subspace_seeds: Vec<SubspaceSeeds>,
+        exclude_words: Vec<String>,
+        matching: &MatchOptions,
+        min_coverage: Option<f64>,
+        definitional_pairs: &DefinitionalPairs,
+        center: CenterEstimator,
+        metric: Metric,
+    ) -> Result<DebiasResult, CoverageError> {
+        let normalized = self.with_space(normalize_space(&self.space));
+        let before = normalized.build_calculator(
+            subspace_seeds.clone(),
+            exclude_words.clone(),
+            matching,
+            min_coverage,
+            Some(definitional_pairs),
+            center,
+            metric,
+        )?;
+        let direction = &before
+            .directional_bias
+            .as_ref()
+            .expect("the definitional pairs give a direction")
+            .direction;
+        let specific_words: Vec<String> = subspace_seeds
+            .iter()
+            .flat_map(|subspace_seed| subspace_seed.seeds.clone())
+            .collect();
+        let dataset = self.with_space(hard_debias(
+            &normalized.space,
+            direction,
+            definitional_pairs,
+            &specific_words,
+            matching,
+        ));
+        let after = dataset.build_calculator(
+            subspace_seeds,
+            exclude_words,
+            matching,
+            min_coverage,
+            Some(definitional_pairs),
+            center,
+            metric,
+        )?;
+        Message::debias_summary(&before, &after);
+        Ok(DebiasResult {
+            before,
+            after,
+            dataset,
+        })
+    }
+
+    /// Erase what a linear classifier can learn to tell the seed tokens of the groups apart
+    pub fn build_inlp(
+        &self,
+        subspace_seeds: Vec<SubspaceSeeds>,
+        matching: &MatchOptions,
+        min_coverage: Option<f64>,
+        iterations: usize,
+    ) -> Result<InlpResult, CoverageError> {
+        let found: Vec<Vec<Token>> = subspace_seeds
+            .iter()
+            .map(|subspace_seed| self.space.find(subspace_seed, matching))
+            .collect();
+        SeedCoverage::check(&subspace_seeds, &found, matching, min_coverage)?;
+        let projection = NullspaceProjection::fit(&found, iterations, matching);
+        let dataset = self.with_space(projection.apply(&self.space));
+        Ok(InlpResult {
+            projection,
+            dataset,
+        })
+    }
+}
+
+// Expose to Python
+#[pymethods]
+impl Dataset {
+    #[allow(clippy::too_many_arguments)]
+    fn calculator(
+        &self,
+        subspace_seeds: Vec<SubspaceSeeds>,
+        exclude_words: Option<Vec<String>>, // words to exclude from random tokens
+        matching: Option<MatchOptions>,     // how words are compared, see `new_match_options`
+        min_coverage: Option<f64>,          // share of the seeds of each subspace that must occur
+        definitional_pairs: Option<Vec<(String, String)>>, // e.g. [("he", "she")], for a bias direction
+        bias_dimension: Option<usize>, // principal components of the pairs kept, 1 by default
+        center: Option<CenterEstimator>, // "mean", "balanced", "trimmed", "geometric_median" or "medoid"
+        metric: Option<Metric>,          // "cosine", "euclidean", "mahalanobis", "dot" or "angular"
+    ) -> PyResult<Calculator> {
+        let definitional_pairs = definitional_pairs
+            .map(|pairs| DefinitionalPairs::new(pairs, bias_dimension.unwrap_or(1)));
+        Ok(self.build_calculator(
+            subspace_seeds,
+            exclude_words.unwrap_or_default(),
+            &matching.unwrap_or_default(),
+            min_coverage,
+            definitional_pairs.as_ref(),
+            center.unwrap_or_default(),
+            metric.unwrap_or_default(),
+        )?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn debias(
+        &self,
+        subspace_seeds: Vec<SubspaceSeeds>,
+        definitional_pairs: Vec<(String, String)>, // e.g. [("he", "she")], the direction removed
+        bias_dimension: Option<usize>, // principal components of the pairs removed, 1 by default
+        exclude_words: Option<Vec<String>>, // words to exclude from random tokens
+        matching: Option<MatchOptions>, // how words are compared, see `new_match_options`
+        min_coverage: Option<f64>,     // share of the seeds of each subspace that must occur
+        output_path: Option<&str>,     // write the debiased tokens to this file
+        center: Option<CenterEstimator>, // "mean", "balanced", "trimmed", "geometric_median" or "medoid"
+        metric: Option<Metric>,          // "cosine", "euclidean", "mahalanobis", "dot" or "angular"
+    ) -> PyResult<DebiasResult> {
+        let definitional_pairs =
+            DefinitionalPairs::new(definitional_pairs, bias_dimension.unwrap_or(1));
+        let result = self.build_debiased(
+            subspace_seeds,
+            exclude_words.unwrap_or_default(),
+            &matching.unwrap_or_default(),
+            min_coverage,
+            &definitional_pairs,
+            center.unwrap_or_default(),
+            metric.unwrap_or_default(),
+        )?;
+        if let Some(output_path) = output_path {
+            result
+                .dataset
+                .space
+                .write(output_path, false)
+                .map_err(|error| {
+                    pyo3::exceptions::PyIOError::new_err(format!("{}: {}", output_path, error))
+                })?;
+            // the PCA the tokens are in, to project new tokens the same way
+            if let Some(pca) = &result.dataset.space.pca {
+                pca.to_file(&format!("{}{}", output_path, constant::PCA_EXTENSION))?;
+            }
+        }
+        Ok(result)
+    }
+
+    fn inlp(
+        &self,
+        subspace_seeds: Vec<SubspaceSeeds>, // the groups to tell apart, at least two
+        iterations: Option<usize>,          // classifiers trained at most, 10 by default
+        matching: Option<MatchOptions>,     // how words are compared, see `new_match_options`
+        min_coverage: Option<f64>,          // share of the seeds of each subspace that must occur
+    ) -> PyResult<InlpResult> {
+        if subspace_seeds.len() < 2 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "INLP needs at least two subspaces to tell apart",
+            ));
+        }
+        Ok(self.build_inlp(
+            subspace_seeds,
+            &matching.unwrap_or_default(),
+            min_coverage,
+            iterations.unwrap_or(constant::INLP_ITERATIONS),
+        )?)
+    }
+
+    fn get_model_name(&self) -> String {
+        self.model_name.clone()
+    }
+
+    fn get_num_lines(&self) -> usize {
+        self.num_lines
+    }
+
+    fn get_num_tokens(&self) -> usize {
+        self.space.tokens.len()
+    }
+
+    fn get_dimension(&self) -> usize {
+        self.space.tokens[0].embedding.len()
+    }
+
+    fn get_pca_dimension(&self) -> Option<usize> {
+        self.pca_dimension
+    }
+
+    fn get_sources(&self) -> Vec<String> {
+        self.sources.clone()
+    }
+
+    /// The PCA the tokens were projected with, to project other datasets the same way
+    fn get_pca(&self) -> Option<PCA> {
+        self.space.pca.clone()
+    }
+
+    /// Fit a PCA on this dataset, without the seeds and excluded words, to project others
+    fn fit_pca(
+        &self,
+        n_components: usize,
+        subspace_seeds: Option<Vec<SubspaceSeeds>>, // their words are left out of the fit
+        exclude_words: Option<Vec<String>>,         // words left out of the fit
+        matching: Option<MatchOptions>, // how words are compared, see `new_match_options`
+    ) -> PyResult<PCA> {
+        let dimension = self.space.tokens[0].embedding.len();
+        if n_components == 0 || n_components > dimension {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "n_components must be between 1 and the dimension {}, got {}",
+                dimension, n_components
+            )));
+        }
+        let mut exclude_words = exclude_words.unwrap_or_default();
+        for subspace_seed in subspace_seeds.unwrap_or_default() {
+            exclude_words.extend(subspace_seed.seeds);
+        }
+        Ok(self.build_pca(n_components, exclude_words, &matching.unwrap_or_default())?)
+    }
+
+    /// The same dataset projected with a PCA, e.g. fitted on another dataset
+    fn project(&self, pca: PCA) -> PyResult<Dataset> {
+        self.build_projected(&pca)
+    }
+
+    fn get_preprocess_report(&self) -> Option<PreprocessReport> {
+        self.preprocess_report.clone()
+    }
+
+    /// Where the word occurs: `(source file, line number, position)` per token
+    fn locate(&self, word: &str) -> Vec<(String, usize, usize)> {
+        self.space
+            .tokens
+            .iter()
+            .filter(|token| token.word == word)
+            .map(|token| {
+                (
+                    self.sources
+                        .get(token.source_id)
+                        .cloned()
+                        .unwrap_or_default(),
+                    token.line_num,
+                    token.position,
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fio::reader::{read_lines, ReadOptions, ReaderType};
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_calculators_share_the_space() {
+        let lines = read_lines(
+            "./test_data/conceptx.json",
+            ReaderType::ConceptX,
+            &ReadOptions::default(),
+        )
+        .unwrap();
+        let dataset = Dataset::new("test".to_string(), lines, Some(2)).unwrap();
+        assert_eq!(dataset.get_dimension(), 2);
+
+        let seeds = |male: &str, female: &str| {
+            vec![
+                SubspaceSeeds::new("male".to_string(), vec![male.to_string()]),
+                SubspaceSeeds::new("female".to_string(), vec![female.to_string()]),
+            ]
+        };
+        let calculator = |male: &str, female: &str| {
+            dataset
+                .build_calculator(
+                    seeds(male, female),
+                    Vec::new(),
+                    &MatchOptions::default(),
+                    None,
+                    None,
+                    CenterEstimator::default(),
+                    Metric::default(),
+                )
+                .unwrap()
+        };
+        let first = calculator("he", "she");
+        // the results keep the PCA, as do the spaces derived from the dataset
+        assert_eq!(first.pca.as_ref().unwrap().dimension(), 4);
+        let inlp = dataset
+            .build_inlp(seeds("he", "she"), &MatchOptions::default(), None, 1)
+            .unwrap();
+        assert!(inlp.dataset.space.pca.is_some());
+        let second = calculator("his", "her");
+        let again = calculator("he", "she");
+        assert_abs_diff_eq!(first.get_bias(), again.get_bias(), epsilon = 1e-9);
+        assert_ne!(first.get_bias(), second.get_bias());
+    }
+
+    #[test]
+    fn test_debias_compares_unit_length_spaces() {
+        let lines = read_lines(
+            "./test_data/conceptx.json",
+            ReaderType::ConceptX,
+            &ReadOptions::default(),
+        )
+        .unwrap();
+        // the same directions, once at unit length and once at uneven lengths
+        let scaled = |scale: &dyn Fn(usize, &[f64]) -> f64| -> Dataset {
+            let mut lines = lines.clone();
+            for (i, token) in lines
+                .iter_mut()
+                .flat_map(|line| &mut line.tokens)
+                .enumerate()
+            {
+                let factor = scale(i, &token.embedding);
+                token.embedding.iter_mut().for_each(|x| *x *= factor);
+            }
+            Dataset::new("test".to_string(), lines, None).unwrap()
+        };
+        let norm = |embedding: &[f64]| embedding.iter().map(|x| x * x).sum::<f64>().sqrt();
+        let unit = scaled(&|_, embedding| 1.0 / norm(embedding));
+        let uneven = scaled(&|i, embedding| (1 + i % 7) as f64 / norm(embedding));
+
+        let seeds = vec![
+            SubspaceSeeds::new(
+                "male".to_string(),
+                vec!["he".to_string(), "man".to_string()],
+            ),
+            SubspaceSeeds::new(
+                "female".to_string(),
+                vec!["her".to_string(), "girl".to_string()],
+            ),
+        ];
+        let pairs = DefinitionalPairs::new(vec![("he".to_string(), "her".to_string())], 1);
+        let debias = |dataset: &Dataset| {
+            dataset
+                .build_debiased(
+                    seeds.clone(),
+                    Vec::new(),
+                    &MatchOptions::default(),
+                    None,
+                    &pairs,
+                    CenterEstimator::default(),
+                    Metric::Euclidean,
+                )
+                .unwrap()
+        };
+        let calculator = |dataset: &Dataset| {
+            dataset
+                .build_calculator(
+                    seeds.clone(),
+                    Vec::new(),
+                    &MatchOptions::default(),
+                    None,
+                    Some(&pairs),
+                    CenterEstimator::default(),
+                    Metric::Euclidean,
+                )
+                .unwrap()
+        };
+        let (unit_result, uneven_result) = (debias(&unit), debias(&uneven));
+        // on a unit length space, `before` is the plain bias
+        assert_abs_diff_eq!(
+            unit_result.before.get_bias(),
+            calculator(&unit).get_bias(),
+            epsilon = 1e-9
+        );
+        // the lengths change neither side of the comparison
+        assert_ne!(calculator(&uneven).get_bias(), calculator(&unit).get_bias());
+        assert_abs_diff_eq!(
+            uneven_result.before.get_bias(),
+            unit_result.before.get_bias(),
+            epsilon = 1e-9
+        );
+        assert_abs_diff_eq!(
+            uneven_result.after.get_bias(),
+            unit_result.after.get_bias(),
+            epsilon = 1e-9
+        );
+    }
+
+    #[test]
+    fn test_empty_subspace_is_an_error() {
+        let lines = read_lines(
+            "./test_data/conceptx.json",
+            ReaderType::ConceptX,
+            &ReadOptions::default(),
+        )
+        .unwrap();
+        let dataset = Dataset::new("test".to_string(), lines, None).unwrap();
+        let seeds = vec![
+            SubspaceSeeds::new("male".to_string(), vec!["he".to_string()]),
+            SubspaceSeeds::new("other".to_string(), vec!["wafflecone".to_string()]),
+        ];
+        let result = dataset.build_calculator(
+            seeds,
+            Vec::new(),
+            &MatchOptions::default(),
+            None,
+            None,
+            CenterEstimator::default(),
+            Metric::default(),
+        );
+        assert!(matches!(result, Err(CoverageError::EmptySubspace { name }) if name == "other"));
+    }
+
+    #[test]
+    fn test_empty_space_is_an_error() {
+        let result = Dataset::new("test".to_string(), Vec::new(), None);
+        assert!(matches!(result, Err(CoverageError::EmptySpace)));
+        let empty_line = Line {
+            tokens: Vec::new(),
+            line_num: 0,
+        };
+        let result = Dataset::new("test".to_string(), vec![empty_line], None);
+        assert!(matches!(result, Err(CoverageError::EmptySpace)));
+    }
+}
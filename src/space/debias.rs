@@ -0,0 +1,213 @@
+use crate::analyizer::calculator::Calculator;
+use crate::embedding::models::Token;
+use crate::space::dataset::Dataset;
+use crate::space::direction::{BiasDirection, DefinitionalPairs};
+use crate::space::matching::{MatchOptions, SeedMatcher};
+use crate::space::space_generator::Space;
+use crate::space::vector::{dot, mean, normalize};
+use crate::space::SpaceGenerator;
+use pyo3::{pyclass, pymethods};
+
+/// Remove the component of the embedding in the bias subspace
+pub fn neutralize(embedding: &[f64], direction: &BiasDirection) -> Vec<f64> {
+    let mut neutral = embedding.to_vec();
+    for component in &direction.components {
+        let projection = dot(embedding, component);
+        for (x, c) in neutral.iter_mut().zip(component) {
+            *x -= projection * c;
+        }
+    }
+    neutral
+}
+
+/// The space with its embeddings scaled to unit length, as hard debiasing assumes
+pub fn normalize_space(space: &Space) -> Space {
+    let tokens: Vec<Token> = space
+        .tokens
+        .iter()
+        .map(|token| token.with_embedding(normalize(&token.embedding)))
+        .collect();
+    Space {
+        pca: space.pca.clone(),
+        ..Space::new(tokens, None, None)
+    }
+}
+
+/// Hard debiasing of Bolukbasi et al. (2016). The embeddings are scaled to unit length,
+/// as the method assumes, then:
+/// - neutralize: every token but the definitional and `specific_words` loses its
+///   component in the bias subspace
+/// - equalize: the occurrences of the words of a pair share the same neutral part,
+///   so the neutral tokens are equally far from both words
+pub fn hard_debias(
+    space: &Space,
+    direction: &BiasDirection,
+    definitional_pairs: &DefinitionalPairs,
+    specific_words: &[String],
+    matching: &MatchOptions,
+) -> Space {
+    let mut embeddings: Vec<Vec<f64>> = space
+        .tokens
+        .iter()
+        .map(|token| normalize(&token.embedding))
+        .collect();
+
+    let mut keep_words = definitional_pairs.words();
+    keep_words.extend(specific_words.iter().cloned());
+    let kept = SeedMatcher::new(&keep_words, matching).mask(&space.tokens);
+    for (embedding, kept) in embeddings.iter_mut().zip(kept) {
+        if !kept {
+            *embedding = normalize(&neutralize(embedding, direction));
+        }
+    }
+
+    for (first, second) in &definitional_pairs.pairs {
+        let first = occurrences(&space.tokens, first, matching);
+        let second = occurrences(&space.tokens, second, matching);
+        if first.is_empty() || second.is_empty() {
+            continue;
+        }
+        // the center of the pair and its part outside of the bias subspace
+        let center: Vec<f64> = mean_of(&embeddings, &first)
+            .iter()
+            .zip(mean_of(&embeddings, &second))
+            .map(|(a, b)| (a + b) / 2.0)
+            .collect();
+        let center_neutral = neutralize(&center, direction);
+        let center_bias: Vec<f64> = center
+            .iter()
+            .zip(&center_neutral)
+            .map(|(c, n)| c - n)
+            .collect();
+        let scale = (1.0 - dot(&center_neutral, &center_neutral))
+            .max(0.0)
+            .sqrt();
+        for &i in first.iter().chain(&second) {
+            let embedding = &embeddings[i];
+            let bias: Vec<f64> = embedding
+                .iter()
+                .zip(neutralize(embedding, direction))
+                .zip(&center_bias)
+                .map(|((x, n), c)| x - n - c)
+                .collect();
+            let norm = dot(&bias, &bias).sqrt();
+            embeddings[i] = center_neutral
+                .iter()
+                .zip(&bias)
+                .map(|(n, b)| if norm > 0.0 { n + scale * b / norm } else { *n })
+                .collect();
+        }
+    }
+
+    let tokens: Vec<Token> = space
+        .tokens
+        .iter()
+        .zip(embeddings)
+        .map(|(token, embedding)| token.with_embedding(embedding))
+        .collect();
+    // still in the basis of the PCA, if any
+    Space {
+        pca: space.pca.clone(),
+        ..Space::new(tokens, None, None)
+    }
+}
+
+/// The bias of a dataset before and after hard debiasing
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct DebiasResult {
+    pub(crate) before: Calculator,
+    pub(crate) after: Calculator,
+    pub(crate) dataset: Dataset,
+}
+
+// Expose to Python
+#[pymethods]
+impl DebiasResult {
+    fn get_before(&self) -> Calculator {
+        self.before.clone()
+    }
+
+    fn get_after(&self) -> Calculator {
+        self.after.clone()
+    }
+
+    /// The debiased tokens, to build more calculators or write them out
+    fn get_dataset(&self) -> Dataset {
+        self.dataset.clone()
+    }
+}
+
+/// The indices of the tokens of the word
+fn occurrences(tokens: &[Token], word: &str, matching: &MatchOptions) -> Vec<usize> {
+    SeedMatcher::new(&[word.to_string()], matching)
+        .matches(tokens)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// The mean of the embeddings at the indices
+fn mean_of(embeddings: &[Vec<f64>], indices: &[usize]) -> Vec<f64> {
+    mean(&indices.iter().map(|&i| &embeddings[i]).collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_hard_debias() {
+        let words = [
+            ("he", vec![0.8, 0.6, 0.0]),
+            ("she", vec![-0.6, 0.6, 0.5]),
+            ("nurse", vec![-0.5, 0.5, 0.5]),
+            ("king", vec![0.9, 0.1, 0.1]),
+        ];
+        let tokens: Vec<Token> = words
+            .iter()
+            .enumerate()
+            .map(|(i, (word, embedding))| Token::new(word.to_string(), i, 0, embedding.clone()))
+            .collect();
+        let space = Space::new(tokens, None, None);
+        let direction = BiasDirection {
+            components: vec![vec![1.0, 0.0, 0.0]],
+            explained_variance: vec![1.0],
+        };
+        let pairs = DefinitionalPairs::new(vec![("he".to_string(), "she".to_string())], 1);
+        let debiased = hard_debias(
+            &space,
+            &direction,
+            &pairs,
+            &["king".to_string()],
+            &MatchOptions::default(),
+        );
+
+        let embedding = |word: &str| {
+            debiased
+                .tokens
+                .iter()
+                .find(|token| token.word == word)
+                .unwrap()
+                .embedding
+                .clone()
+        };
+        // neutralized
+        assert_abs_diff_eq!(embedding("nurse")[0], 0.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(
+            dot(&embedding("nurse"), &embedding("nurse")),
+            1.0,
+            epsilon = 1e-9
+        );
+        // equalized: opposite on the direction, the same elsewhere, unit length
+        let (he, she) = (embedding("he"), embedding("she"));
+        assert_abs_diff_eq!(he[0], -she[0], epsilon = 1e-9);
+        assert_abs_diff_eq!(he[1], she[1], epsilon = 1e-9);
+        assert_abs_diff_eq!(he[2], she[2], epsilon = 1e-9);
+        assert_abs_diff_eq!(dot(&he, &he), 1.0, epsilon = 1e-9);
+        assert!(he[0] > 0.0);
+        // kept
+        assert!(embedding("king")[0] > 0.9);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::embedding::models::Token;
+use crate::embedding::pooling::{pool_embeddings, Pooling};
+use crate::space::coverage::CoverageError;
+use crate::space::matching::{MatchOptions, SeedMatcher};
+use crate::space::vector::dot;
+use crate::util::Message;
+use nalgebra::{DMatrix, SVD};
+use std::collections::HashMap;
+
+/// Pairs of words differing only by the bias, e.g. ("he", "she"), defining its direction
+#[derive(Debug, Clone)]
+pub struct DefinitionalPairs {
+    pub pairs: Vec<(String, String)>,
+    /// The number of principal components kept, 1 for a single direction
+    pub dimension: usize,
+}
+
+impl DefinitionalPairs {
+    pub fn new(pairs: Vec<(String, String)>, dimension: usize) -> Self {
+        DefinitionalPairs { pairs, dimension }
+    }
+
+    pub fn words(&self) -> Vec<String> {
+        self.pairs
+            .iter()
+            .flat_map(|(first, second)| [first.clone(), second.clone()])
+            .collect()
+    }
+}
+
+/// The bias subspace: the principal components of the differences within the pairs,
+/// as in Bolukbasi et al. (2016)
+#[derive(Debug, Clone)]
+pub struct BiasDirection {
+    /// Orthonormal, the first one points to the first word of the pairs
+    pub(crate) components: Vec<Vec<f64>>,
+    /// The share of the variance of the pair differences each component explains
+    pub(crate) explained_variance: Vec<f64>,
+}
+
+impl BiasDirection {
+    /// Each pair is centered on its mean, then the principal components of the centered
+    /// words are kept. Pairs with a word missing from the tokens are skipped.
+    pub fn from_pairs(
+        tokens: &[Token],
+        definitional_pairs: &DefinitionalPairs,
+        matching: &MatchOptions,
+    ) -> Result<Self, CoverageError> {
+        let mut rows: Vec<f64> = Vec::new();
+        let mut differences: Vec<Vec<f64>> = Vec::new();
+        for (first, second) in &definitional_pairs.pairs {
+            let (Some(a), Some(b)) = (
+                mean_embedding(tokens, first, matching),
+                mean_embedding(tokens, second, matching),
+            ) else {
+                continue;
+            };
+            for (x, y) in a.iter().zip(&b) {
+                rows.push((x - y) / 2.0);
+            }
+            for (x, y) in a.iter().zip(&b) {
+                rows.push((y - x) / 2.0);
+            }
+            differences.push(a.iter().zip(&b).map(|(x, y)| x - y).collect());
+        }
+        let dimension = definitional_pairs.dimension;
+        Message::definitional_pairs(differences.len(), definitional_pairs.pairs.len());
+        // the two rows of a pair are opposite, each pair adds at most one component
+        if dimension == 0 || differences.len() < dimension {
+            return Err(CoverageError::DefinitionalPairs {
+                found: differences.len(),
+                dimension,
+            });
+        }
+
+        let num_features = tokens[0].embedding.len();
+        let centered = DMatrix::from_row_slice(rows.len() / num_features, num_features, &rows);
+        // decomposed through the scatter matrix, as in the PCA: the SVD of the wide and
+        // rank deficient matrix of the pairs can return a wrong top singular vector
+        let scatter = centered.transpose() * &centered;
+        let svd = SVD::new(scatter, false, true);
+        let v_t = svd.v_t.expect("the right singular vectors are computed");
+        let mut order: Vec<usize> = (0..svd.singular_values.len()).collect();
+        order.sort_by(|&i, &j| svd.singular_values[j].total_cmp(&svd.singular_values[i]));
+        let total: f64 = svd.singular_values.iter().sum();
+
+        let mut components: Vec<Vec<f64>> = Vec::new();
+        let mut explained_variance: Vec<f64> = Vec::new();
+        for &i in order.iter().take(dimension) {
+            let mut component: Vec<f64> = v_t.row(i).iter().cloned().collect();
+            // the sign of a singular vector is arbitrary, orient it by the pairs
+            let orientation: f64 = differences.iter().map(|d| dot(d, &component)).sum();
+            if orientation < 0.0 {
+                component.iter_mut().for_each(|x| *x = -*x);
+            }
+            components.push(component);
+            explained_variance.push(svd.singular_values[i] / total);
+        }
+        Ok(BiasDirection {
+            components,
+            explained_variance,
+        })
+    }
+
+    /// The signed cosine between the embedding and the first direction,
+    /// positive towards the first word of the pairs
+    pub fn projection(&self, embedding: &[f64]) -> f64 {
+        let norm = dot(embedding, embedding).sqrt();
+        if norm == 0.0 {
+            return 0.0;
+        }
+        dot(embedding, &self.components[0]) / norm
+    }
+
+    /// The share of the norm of the embedding in the bias subspace, the absolute
+    /// projection when it is a single direction
+    pub fn subspace_projection(&self, embedding: &[f64]) -> f64 {
+        let norm = dot(embedding, embedding).sqrt();
+        if norm == 0.0 {
+            return 0.0;
+        }
+        let in_subspace: f64 = self
+            .components
+            .iter()
+            .map(|component| dot(embedding, component).powi(2))
+            .sum();
+        in_subspace.sqrt() / norm
+    }
+}
+
+/// The projection of the neutral tokens on the bias direction
+#[derive(Debug, Clone)]
+pub struct DirectionalBias {
+    pub(crate) direction: BiasDirection,
+    /// The mean signed projection of the occurrences of each word
+    pub(crate) projection_per_token: HashMap<String, f64>,
+    /// The mean absolute projection of the neutral tokens (DirectBias with c = 1)
+    pub(crate) direct_bias: f64,
+}
+
+impl DirectionalBias {
+    pub fn new(direction: BiasDirection, neutral_tokens: &[Token]) -> Self {
+        let mut projections: HashMap<String, (f64, usize)> = HashMap::new();
+        let mut direct_bias = 0.0;
+        for token in neutral_tokens {
+            let entry = projections.entry(token.word.clone()).or_insert((0.0, 0));
+            entry.0 += direction.projection(&token.embedding);
+            entry.1 += 1;
+            direct_bias += direction.subspace_projection(&token.embedding);
+        }
+        DirectionalBias {
+            projection_per_token: projections
+                .into_iter()
+                .map(|(word, (sum, count))| (word, sum / count as f64))
+                .collect(),
+            direct_bias: direct_bias / neutral_tokens.len().max(1) as f64,
+            direction,
+        }
+    }
+}
+
+fn mean_embedding(tokens: &[Token], word: &str, matching: &MatchOptions) -> Option<Vec<f64>> {
+    let found = SeedMatcher::new(&[word.to_string()], matching).find(tokens);
+    if found.is_empty() {
+        return None;
+    }
+    let pieces: Vec<&Token> = found.iter().collect();
+    Some(pool_embeddings(&pieces, Pooling::Mean))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn tokens() -> Vec<Token> {
+        let words = [
+            ("he", vec![1.0, 1.0]),
+            ("she", vec![-1.0, 1.0]),
+            ("man", vec![2.0, 0.5]),
+            ("woman", vec![-2.0, 0.5]),
+            ("nurse", vec![-1.0, 1.0]),
+            ("table", vec![0.0, 3.0]),
+        ];
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, (word, embedding))| Token::new(word.to_string(), i, 0, embedding.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_direction() {
+        let pairs = DefinitionalPairs::new(
+            vec![
+                ("he".to_string(), "she".to_string()),
+                ("man".to_string(), "woman".to_string()),
+                ("king".to_string(), "queen".to_string()),
+            ],
+            1,
+        );
+        let direction =
+            BiasDirection::from_pairs(&tokens(), &pairs, &MatchOptions::default()).unwrap();
+        assert_abs_diff_eq!(direction.components[0][0], 1.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(direction.components[0][1], 0.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(direction.explained_variance[0], 1.0, epsilon = 1e-9);
+
+        let neutral: Vec<Token> = tokens().into_iter().skip(4).collect();
+        let bias = DirectionalBias::new(direction, &neutral);
+        assert_abs_diff_eq!(
+            bias.projection_per_token["nurse"],
+            -(0.5f64.sqrt()),
+            epsilon = 1e-9
+        );
+        assert_abs_diff_eq!(bias.projection_per_token["table"], 0.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(bias.direct_bias, 0.5f64.sqrt() / 2.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_direction_of_one_pair() {
+        // a single pair whose SVD, taken directly, gave a wrong top singular vector
+        let he = [0.1639089, 0.1129924, 0.1006351, 0.2476380];
+        let her = [-0.0142675, 0.2043429, -0.5967806, 0.5195551];
+        let tokens = vec![
+            Token::new("he".to_string(), 0, 0, he.to_vec()),
+            Token::new("her".to_string(), 1, 0, her.to_vec()),
+        ];
+        let pairs = DefinitionalPairs::new(vec![("he".to_string(), "her".to_string())], 1);
+        let direction =
+            BiasDirection::from_pairs(&tokens, &pairs, &MatchOptions::default()).unwrap();
+        let difference: Vec<f64> = he.iter().zip(&her).map(|(a, b)| a - b).collect();
+        let norm = dot(&difference, &difference).sqrt();
+        for (component, d) in direction.components[0].iter().zip(&difference) {
+            assert_abs_diff_eq!(*component, d / norm, epsilon = 1e-9);
+        }
+        assert_abs_diff_eq!(direction.explained_variance[0], 1.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_too_few_pairs() {
+        let pairs = DefinitionalPairs::new(vec![("he".to_string(), "she".to_string())], 2);
+        assert!(matches!(
+            BiasDirection::from_pairs(&tokens(), &pairs, &MatchOptions::default()),
+            Err(CoverageError::DefinitionalPairs { found: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::embedding::models::Token;
+use crate::space::dataset::Dataset;
+use crate::space::matching::MatchOptions;
+use crate::space::space_generator::Space;
+use crate::space::SpaceGenerator;
+use crate::util::classifier::LogisticRegression;
+use crate::util::constant;
+use crate::util::Message;
+use nalgebra::{DMatrix, DVector, RowDVector};
+use pyo3::{pyclass, pymethods};
+use std::collections::HashMap;
+
+/// Iterative nullspace projection of Ravfogel et al. (2020): a linear classifier is
+/// trained to tell the groups apart, the embeddings are projected on the nullspace of
+/// its weights, and again until the groups cannot be told apart anymore.
+#[derive(Debug, Clone)]
+pub struct NullspaceProjection {
+    /// Symmetric, the identity minus the directions removed by all the iterations
+    pub(crate) projection: DMatrix<f64>,
+    /// The accuracy of the classifier of each iteration, on held out seed tokens
+    pub(crate) accuracy: Vec<f64>,
+    /// The accuracy of always predicting the largest group
+    pub(crate) baseline: f64,
+}
+
+impl NullspaceProjection {
+    /// `groups` holds the seed tokens of each group, at least two groups
+    pub fn fit(groups: &[Vec<Token>], iterations: usize, matching: &MatchOptions) -> Self {
+        let mut rows: Vec<RowDVector<f64>> = Vec::new();
+        let mut labels: Vec<usize> = Vec::new();
+        for (label, tokens) in groups.iter().enumerate() {
+            for token in tokens {
+                rows.push(RowDVector::from_vec(token.embedding.clone()));
+                labels.push(label);
+            }
+        }
+        let dimension = rows[0].len();
+        let x = DMatrix::from_rows(&rows);
+
+        // the occurrences of a held out word are all in the test set, so the accuracy
+        // does not reward remembering the words
+        let held_out_tokens = held_out(groups, matching);
+        let hold_out = labels.len() >= constant::INLP_HOLD_OUT_FROM
+            && held_out_tokens.contains(&true)
+            && held_out_tokens.contains(&false);
+        let split = |held_out: bool| -> (DMatrix<f64>, Vec<usize>) {
+            let indices: Vec<usize> = (0..labels.len())
+                .filter(|&i| !hold_out || held_out_tokens[i] == held_out)
+                .collect();
+            (
+                x.select_rows(&indices),
+                indices.iter().map(|&i| labels[i]).collect(),
+            )
+        };
+        let (x_train, y_train) = split(false);
+        let (x_test, y_test) = split(true);
+        let baseline = (0..groups.len())
+            .map(|label| y_test.iter().filter(|&&y| y == label).count())
+            .max()
+            .unwrap_or(0) as f64
+            / y_test.len() as f64;
+
+        let mut basis: Vec<DVector<f64>> = Vec::new();
+        let mut projection = DMatrix::identity(dimension, dimension);
+        let mut accuracy = Vec::new();
+        for iteration in 0..iterations {
+            let classifier =
+                LogisticRegression::new(groups.len(), constant::INLP_EPOCHS, constant::INLP_L2)
+                    .fit(&(&x_train * &projection), &y_train);
+            let score = classifier.accuracy(&(&x_test * &projection), &y_test);
+            Message::inlp_iteration(iteration, score, baseline);
+            accuracy.push(score);
+            if score <= baseline {
+                break;
+            }
+
+            // the span of the weights joins the removed subspace
+            let mut removed = 0;
+            for row in classifier.weights().row_iter() {
+                let mut direction: DVector<f64> = &projection * row.transpose();
+                for base in &basis {
+                    direction -= base * base.dot(&direction);
+                }
+                let norm = direction.norm();
+                if norm > constant::INLP_RANK_TOLERANCE {
+                    basis.push(direction / norm);
+                    removed += 1;
+                }
+            }
+            if removed == 0 {
+                break;
+            }
+            projection = DMatrix::identity(dimension, dimension);
+            for base in &basis {
+                projection -= base * base.transpose();
+            }
+        }
+        NullspaceProjection {
+            projection,
+            accuracy,
+            baseline,
+        }
+    }
+
+    pub fn apply(&self, space: &Space) -> Space {
+        let tokens: Vec<Token> = space
+            .tokens
+            .iter()
+            .map(|token| {
+                let embedding = &self.projection * DVector::from_vec(token.embedding.clone());
+                token.with_embedding(embedding.iter().cloned().collect())
+            })
+            .collect();
+        // projected within the basis of the PCA, if any
+        Space {
+            pca: space.pca.clone(),
+            ..Space::new(tokens, None, None)
+        }
+    }
+
+    /// The number of dimensions removed
+    pub fn rank(&self) -> usize {
+        let dimension = self.projection.nrows();
+        dimension - self.projection.trace().round() as usize
+    }
+}
+
+/// Whether each token, group after group, is held out: every fifth word of each group,
+/// in the order the words first occur
+fn held_out(groups: &[Vec<Token>], matching: &MatchOptions) -> Vec<bool> {
+    let mut held_out = Vec::new();
+    for tokens in groups {
+        let mut words: HashMap<String, bool> = HashMap::new();
+        for token in tokens {
+            let index = words.len();
+            held_out.push(
+                *words
+                    .entry(matching.key(&token.word))
+                    .or_insert(index % 5 == 4),
+            );
+        }
+    }
+    held_out
+}
+
+/// The projection removing the group information and the projected dataset
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct InlpResult {
+    pub(crate) projection: NullspaceProjection,
+    pub(crate) dataset: Dataset,
+}
+
+// Expose to Python
+#[pymethods]
+impl InlpResult {
+    /// The (dimension, dimension) matrix applied to the embeddings
+    fn get_projection(&self) -> Vec<Vec<f64>> {
+        self.projection
+            .projection
+            .row_iter()
+            .map(|row| row.iter().cloned().collect())
+            .collect()
+    }
+
+    /// The accuracy of the classifier of each iteration
+    fn get_accuracy(&self) -> Vec<f64> {
+        self.projection.accuracy.clone()
+    }
+
+    /// The accuracy of always predicting the largest group
+    fn get_baseline(&self) -> f64 {
+        self.projection.baseline
+    }
+
+    fn get_rank(&self) -> usize {
+        self.projection.rank()
+    }
+
+    /// The projected tokens, to build calculators
+    fn get_dataset(&self) -> Dataset {
+        self.dataset.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_group_information_is_removed() {
+        // the groups differ on the first two dimensions, the third one is noise
+        let group = |sign: f64| -> Vec<Token> {
+            (0..20)
+                .map(|i| {
+                    let noise = (i as f64 * 0.7).sin();
+                    let embedding = vec![sign * (1.0 + 0.1 * noise), sign * 0.5, noise];
+                    Token::new(format!("w{}", i % 10), i, 0, embedding)
+                })
+                .collect()
+        };
+        let groups = vec![group(1.0), group(-1.0)];
+        let inlp = NullspaceProjection::fit(&groups, 5, &MatchOptions::default());
+        assert_eq!(inlp.accuracy[0], 1.0);
+        assert!(*inlp.accuracy.last().unwrap() <= inlp.baseline);
+        assert!(inlp.rank() >= 1);
+
+        // the projection is idempotent
+        let twice = &inlp.projection * &inlp.projection;
+        assert!((twice - &inlp.projection).norm() < 1e-9);
+    }
+
+    #[test]
+    fn test_words_are_held_out_whole() {
+        let tokens: Vec<Token> = ["he", "He", "him", "his", "himself", "man", "man"]
+            .iter()
+            .enumerate()
+            .map(|(i, word)| Token::new(word.to_string(), i, 0, vec![0.0]))
+            .collect();
+        let groups = vec![tokens];
+        assert_eq!(
+            held_out(&groups, &MatchOptions::default()),
+            vec![false, false, false, false, true, false, false]
+        );
+        // "He" is "he" with case folding, "man" becomes the fifth word
+        assert_eq!(
+            held_out(&groups, &MatchOptions::new(true, false, false)),
+            vec![false, false, false, false, false, true, true]
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use crate::fio::seeds::{parse_seeds, SeedFormat};
+use crate::space::seeds::SubspaceSeeds;
+
+/// The seed lists shipped with the crate, named `<topic>/<lexicon>-v<version>`.
+/// A published version is never edited, a changed list gets a new version.
+const LEXICONS: &[(&str, &str)] = &[
+    (
+        "age/young-old-v1",
+        include_str!("../../lexicons/age/young-old-v1.toml"),
+    ),
+    (
+        "gender/binary-v1",
+        include_str!("../../lexicons/gender/binary-v1.toml"),
+    ),
+    (
+        "nationality/continents-v1",
+        include_str!("../../lexicons/nationality/continents-v1.toml"),
+    ),
+    (
+        "race/names-v1",
+        include_str!("../../lexicons/race/names-v1.toml"),
+    ),
+    (
+        "religion/abrahamic-v1",
+        include_str!("../../lexicons/religion/abrahamic-v1.toml"),
+    ),
+];
+
+pub fn list_lexicons() -> Vec<String> {
+    LEXICONS.iter().map(|(name, _)| name.to_string()).collect()
+}
+
+/// The groups of a built-in lexicon, `None` if there is no lexicon of that name
+pub fn builtin_seeds(name: &str) -> Option<Vec<SubspaceSeeds>> {
+    LEXICONS
+        .iter()
+        .find(|(lexicon, _)| *lexicon == name)
+        .map(|(lexicon, text)| {
+            parse_seeds(text, SeedFormat::Toml, lexicon)
+                .unwrap_or_else(|error| panic!("invalid built-in lexicon: {}", error))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builtin_lexicons_parse() {
+        for name in list_lexicons() {
+            let groups = builtin_seeds(&name).unwrap();
+            assert!(groups.len() >= 2, "{}", name);
+            assert!(groups.iter().all(|group| !group.seeds.is_empty()));
+        }
+        assert!(builtin_seeds("gender/binary-v0").is_none());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::embedding::models::Token;
+use crate::embedding::pooling::{pool_embeddings, Pooling};
+use pyo3::pyclass;
+use std::collections::HashSet;
+use std::ops::Range;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// How tokens are compared to the seeds and excluded words. Both sides go through the
+/// same `key`, so e.g. with case folding the seed "he" matches "He" and "HE".
+#[pyclass]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchOptions {
+    pub(crate) case_fold: bool,
+    pub(crate) nfkc: bool,
+    pub(crate) strip_accents: bool,
+}
+
+impl MatchOptions {
+    pub fn new(case_fold: bool, nfkc: bool, strip_accents: bool) -> Self {
+        MatchOptions {
+            case_fold,
+            nfkc,
+            strip_accents,
+        }
+    }
+
+    /// The form of the word that is compared
+    pub fn key(&self, word: &str) -> String {
+        let mut key = if self.nfkc {
+            word.nfkc().collect()
+        } else {
+            word.to_string()
+        };
+        if self.strip_accents {
+            key = key.nfd().filter(|c| !is_combining_mark(*c)).nfc().collect();
+        }
+        if self.case_fold {
+            key = key.to_lowercase();
+        }
+        key
+    }
+}
+
+impl std::fmt::Display for MatchOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut options = Vec::new();
+        if self.case_fold {
+            options.push("case folded");
+        }
+        if self.nfkc {
+            options.push("NFKC");
+        }
+        if self.strip_accents {
+            options.push("accents stripped");
+        }
+        if options.is_empty() {
+            write!(f, "exact")
+        } else {
+            write!(f, "{}", options.join(", "))
+        }
+    }
+}
+
+/// Finds seeds in a sequence of tokens. A seed with several words, e.g. "police officer",
+/// matches consecutive positions of one line, or a single token holding the whole phrase
+/// as static embeddings have for "new york".
+pub struct SeedMatcher {
+    words: HashSet<String>,
+    phrases: Vec<Vec<String>>,
+    matching: MatchOptions,
+}
+
+impl SeedMatcher {
+    pub fn new(seeds: &[String], matching: &MatchOptions) -> Self {
+        let mut words = HashSet::new();
+        let mut phrases = Vec::new();
+        for seed in seeds {
+            let key = matching.key(seed);
+            let parts: Vec<String> = key.split_whitespace().map(str::to_string).collect();
+            if parts.len() > 1 {
+                phrases.push(parts);
+            }
+            words.insert(key);
+        }
+        SeedMatcher {
+            words,
+            phrases,
+            matching: matching.clone(),
+        }
+    }
+
+    /// The token ranges matching a seed, ordered by their start
+    pub fn matches(&self, tokens: &[Token]) -> Vec<Range<usize>> {
+        let keys: Vec<String> = tokens
+            .iter()
+            .map(|token| self.matching.key(&token.word))
+            .collect();
+        let mut matches = Vec::new();
+        for start in 0..tokens.len() {
+            if self.words.contains(&keys[start]) {
+                matches.push(start..start + 1);
+            }
+            for phrase in &self.phrases {
+                let end = start + phrase.len();
+                if end <= tokens.len()
+                    && keys[start..end] == phrase[..]
+                    && is_consecutive(&tokens[start..end])
+                {
+                    matches.push(start..end);
+                }
+            }
+        }
+        matches
+    }
+
+    /// The matched tokens, a phrase being mean pooled into one token
+    pub fn find(&self, tokens: &[Token]) -> Vec<Token> {
+        self.matches(tokens)
+            .into_iter()
+            .map(|range| match range.len() {
+                1 => tokens[range.start].clone(),
+                _ => pool_phrase(&tokens[range]),
+            })
+            .collect()
+    }
+
+    /// Whether each token is part of a match
+    pub fn mask(&self, tokens: &[Token]) -> Vec<bool> {
+        let mut mask = vec![false; tokens.len()];
+        for range in self.matches(tokens) {
+            mask[range].iter_mut().for_each(|matched| *matched = true);
+        }
+        mask
+    }
+}
+
+/// Adjacent words of the same line of the same file
+fn is_consecutive(tokens: &[Token]) -> bool {
+    tokens.windows(2).all(|pair| {
+        pair[0].source_id == pair[1].source_id
+            && pair[0].line_num == pair[1].line_num
+            && pair[0].position + 1 == pair[1].position
+    })
+}
+
+fn pool_phrase(tokens: &[Token]) -> Token {
+    let pieces: Vec<&Token> = tokens.iter().collect();
+    let join = |word: fn(&Token) -> &str| -> String {
+        tokens.iter().map(word).collect::<Vec<&str>>().join(" ")
+    };
+    let mut token = tokens[0].with_embedding(pool_embeddings(&pieces, Pooling::Mean));
+    token.word = join(|token| &token.word);
+    token.raw_word = join(|token| &token.raw_word);
+    token.update_id();
+    token
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tokens(words: &[&str]) -> Vec<Token> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| Token::new(word.to_string(), i, 0, vec![i as f64]))
+            .collect()
+    }
+
+    #[test]
+    fn test_phrases() {
+        let tokens = tokens(&["a", "police", "officer", "and", "police"]);
+        let matching = MatchOptions::default();
+        let seeds = vec!["police officer".to_string()];
+        let matcher = SeedMatcher::new(&seeds, &matching);
+        let found = matcher.find(&tokens);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].word, "police officer");
+        assert_eq!(found[0].embedding, vec![1.5]);
+        assert_eq!(matcher.mask(&tokens), vec![false, true, true, false, false]);
+    }
+
+    #[test]
+    fn test_phrases_stay_in_one_line() {
+        let mut tokens = tokens(&["police", "officer"]);
+        tokens[1].line_num = 1;
+        tokens[1].position = 0;
+        let matching = MatchOptions::default();
+        let seeds = vec!["police officer".to_string()];
+        assert!(SeedMatcher::new(&seeds, &matching).find(&tokens).is_empty());
+    }
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(MatchOptions::default().key("He"), "He");
+        assert_eq!(MatchOptions::new(true, false, false).key("He"), "he");
+        assert_eq!(MatchOptions::new(false, true, false).key("ｈｅ"), "he");
+        assert_eq!(
+            MatchOptions::new(true, false, true).key("Fiancée"),
+            "fiancee"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(MatchOptions::default().to_string(), "exact");
+        assert_eq!(
+            MatchOptions::new(true, false, true).to_string(),
+            "case folded, accents stripped"
+        );
+    }
+}
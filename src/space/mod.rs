@@ -1,8 +1,20 @@
+pub mod center;
+pub mod coverage;
+pub mod dataset;
+pub mod debias;
+pub mod direction;
+pub mod inlp;
+pub mod lexicon;
+pub mod matching;
+pub mod preprocess;
 pub mod seeds;
 pub mod space_generator;
+pub mod stream;
+pub mod vector;
 
 use super::Token;
 use crate::embedding::models::TokenOperators;
+use matching::MatchOptions;
 use seeds::SubspaceSeeds;
 
 pub trait SpaceGenerator {
@@ -11,10 +23,14 @@ pub trait SpaceGenerator {
         words_of_interests: Option<SubspaceSeeds>,
         pca_dimension: Option<usize>,
     ) -> Self;
+    #[allow(dead_code)]
     fn set_space_name(&mut self, name: String);
-    fn find(&self, subspace_seed: &SubspaceSeeds) -> Vec<Token>;
+    fn find(&self, subspace_seed: &SubspaceSeeds, matching: &MatchOptions) -> Vec<Token>;
+    #[allow(dead_code)]
     fn get_center(&self) -> Vec<f64>;
+    #[allow(dead_code)]
     fn get_std(&self) -> Vec<f64>;
-    fn get_neutral_tokens(&self, exclude: Vec<String>) -> Vec<Token>;
+    fn get_neutral_tokens(&self, exclude: Vec<String>, matching: &MatchOptions) -> Vec<Token>;
+    #[allow(dead_code)]
     fn print_summary(&self);
 }
@@ -0,0 +1,325 @@
+use crate::embedding::models::Token;
+use crate::space::space_generator::Space;
+use crate::space::vector::{dot, mean, std};
+use crate::space::SpaceGenerator;
+use crate::util::constant;
+use crate::util::pca::PCA;
+use crate::util::Message;
+use nalgebra::{DMatrix, RowDVector};
+use pyo3::{pyclass, pymethods};
+
+/// Corrections of the anisotropy of the global space, applied before the neutral and group
+/// spaces are taken from it. Contextual embeddings share a dominant direction, so every
+/// cosine similarity is close to 1 and the softmax over the groups is nearly uniform.
+#[pyclass]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Preprocessing {
+    /// Subtract the mean of the space
+    pub(crate) center: bool,
+    /// Divide each dimension by its standard deviation, after centering
+    pub(crate) standardize: bool,
+    /// "All-but-the-top" of Mu and Viswanath (2018): remove the projection on the first
+    /// principal components of the centered space
+    pub(crate) remove_top: usize,
+    /// Scale down the dimensions dominating the cosine similarity (Timkey and van Schijndel, 2021)
+    pub(crate) rogue_dimensions: bool,
+}
+
+impl Preprocessing {
+    pub fn new(center: bool, standardize: bool, remove_top: usize, rogue_dimensions: bool) -> Self {
+        Preprocessing {
+            center,
+            standardize,
+            remove_top,
+            rogue_dimensions,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Preprocessing::default()
+    }
+
+    /// The rogue dimensions are scaled first, on the raw embeddings where they show,
+    /// then the space is centered, standardized and its top components removed
+    pub fn apply(&self, space: &Space) -> (Space, PreprocessReport) {
+        let anisotropy_before = anisotropy(&space.tokens);
+        let mut embeddings: Vec<Vec<f64>> = space
+            .tokens
+            .iter()
+            .map(|token| token.embedding.clone())
+            .collect();
+
+        let rogue_dimensions = if self.rogue_dimensions {
+            let rogue = rogue_dimensions(&space.tokens);
+            scale_down(&mut embeddings, &rogue);
+            rogue
+        } else {
+            Vec::new()
+        };
+        if self.center || self.standardize || self.remove_top > 0 {
+            let center = mean(&embeddings);
+            for embedding in embeddings.iter_mut() {
+                for (x, c) in embedding.iter_mut().zip(&center) {
+                    *x -= c;
+                }
+            }
+        }
+        if self.standardize {
+            let std = std(&embeddings);
+            for embedding in embeddings.iter_mut() {
+                for (x, s) in embedding.iter_mut().zip(&std) {
+                    if *s > 0.0 {
+                        *x /= s;
+                    }
+                }
+            }
+        }
+        let removed_variance = if self.remove_top > 0 {
+            remove_top(&mut embeddings, self.remove_top)
+        } else {
+            Vec::new()
+        };
+
+        let space = rebuild(space, &embeddings);
+        let report = PreprocessReport {
+            preprocessing: self.clone(),
+            rogue_dimensions,
+            removed_variance,
+            anisotropy_before,
+            anisotropy_after: anisotropy(&space.tokens),
+        };
+        Message::preprocessing(&report);
+        (space, report)
+    }
+}
+
+impl std::fmt::Display for Preprocessing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut steps = Vec::new();
+        if self.rogue_dimensions {
+            steps.push("rogue dimensions scaled down".to_string());
+        }
+        if self.center {
+            steps.push("centered".to_string());
+        }
+        if self.standardize {
+            steps.push("standardized".to_string());
+        }
+        if self.remove_top > 0 {
+            steps.push(format!("top {} components removed", self.remove_top));
+        }
+        if steps.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", steps.join(", "))
+        }
+    }
+}
+
+/// What the preprocessing did to the space
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct PreprocessReport {
+    pub(crate) preprocessing: Preprocessing,
+    pub(crate) rogue_dimensions: Vec<usize>,
+    /// The share of the variance of the centered space carried by each removed component
+    pub(crate) removed_variance: Vec<f64>,
+    pub(crate) anisotropy_before: f64,
+    pub(crate) anisotropy_after: f64,
+}
+
+// Expose to Python
+#[pymethods]
+impl PreprocessReport {
+    fn get_steps(&self) -> String {
+        self.preprocessing.to_string()
+    }
+
+    fn get_rogue_dimensions(&self) -> Vec<usize> {
+        self.rogue_dimensions.clone()
+    }
+
+    fn get_removed_variance(&self) -> Vec<f64> {
+        self.removed_variance.clone()
+    }
+
+    /// The average cosine similarity between two different tokens, before the preprocessing
+    fn get_anisotropy_before(&self) -> f64 {
+        self.anisotropy_before
+    }
+
+    fn get_anisotropy_after(&self) -> f64 {
+        self.anisotropy_after
+    }
+}
+
+/// The average cosine similarity over all pairs of different tokens, 0 for an isotropic space
+pub fn anisotropy(tokens: &[Token]) -> f64 {
+    let n = tokens.len() as f64;
+    if tokens.len() < 2 {
+        return 0.0;
+    }
+    let (sum, squares) = unit_sums(tokens);
+    (dot(&sum, &sum) - squares.iter().sum::<f64>()) / (n * (n - 1.0))
+}
+
+/// The dimensions whose share of the anisotropy is `ROGUE_DIMENSION_FACTOR` times
+/// larger than an even share
+pub fn rogue_dimensions(tokens: &[Token]) -> Vec<usize> {
+    let total = anisotropy(tokens);
+    if tokens.len() < 2 || total <= 0.0 {
+        return Vec::new();
+    }
+    let (sum, squares) = unit_sums(tokens);
+    let dimension = sum.len() as f64;
+    sum.iter()
+        .zip(&squares)
+        .map(|(s, q)| s * s - q)
+        .enumerate()
+        .filter(|(_, contribution)| {
+            let n = tokens.len() as f64;
+            contribution / (n * (n - 1.0)) / total > constant::ROGUE_DIMENSION_FACTOR / dimension
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// The sum over the tokens of their unit vectors, and of their squares, per dimension
+fn unit_sums(tokens: &[Token]) -> (Vec<f64>, Vec<f64>) {
+    let dimension = tokens[0].embedding.len();
+    let mut sum = vec![0.0; dimension];
+    let mut squares = vec![0.0; dimension];
+    for token in tokens {
+        let norm = dot(&token.embedding, &token.embedding).sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        for ((s, q), x) in sum.iter_mut().zip(squares.iter_mut()).zip(&token.embedding) {
+            *s += x / norm;
+            *q += (x / norm).powi(2);
+        }
+    }
+    (sum, squares)
+}
+
+/// Scale each rogue dimension so its root mean square is the median one of all dimensions
+fn scale_down(embeddings: &mut [Vec<f64>], rogue: &[usize]) {
+    if rogue.is_empty() {
+        return;
+    }
+    let n = embeddings.len() as f64;
+    let rms: Vec<f64> = (0..embeddings[0].len())
+        .map(|i| {
+            (embeddings
+                .iter()
+                .map(|embedding| embedding[i].powi(2))
+                .sum::<f64>()
+                / n)
+                .sqrt()
+        })
+        .collect();
+    let mut sorted = rms.clone();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    for &i in rogue {
+        let scale = if rms[i] > 0.0 {
+            (median / rms[i]).min(1.0)
+        } else {
+            1.0
+        };
+        for embedding in embeddings.iter_mut() {
+            embedding[i] *= scale;
+        }
+    }
+}
+
+/// Remove the projection of the centered embeddings on their first principal components,
+/// and return the share of the variance each of them carried
+fn remove_top(embeddings: &mut [Vec<f64>], n_components: usize) -> Vec<f64> {
+    let n_components = n_components.min(embeddings[0].len());
+    let x = DMatrix::from_rows(
+        &embeddings
+            .iter()
+            .map(|embedding| RowDVector::from_row_slice(embedding))
+            .collect::<Vec<RowDVector<f64>>>(),
+    );
+    let total = x.norm_squared();
+    let pca = PCA::new(n_components).fit(x);
+
+    // one embedding at a time, without the matrices of all the scores and projections
+    let mut removed = vec![0.0; n_components];
+    for embedding in embeddings.iter_mut() {
+        let scores = pca.project(embedding);
+        for ((score, removed), component) in scores
+            .iter()
+            .zip(removed.iter_mut())
+            .zip(pca.components().column_iter())
+        {
+            *removed += score * score;
+            for (value, c) in embedding.iter_mut().zip(component.iter()) {
+                *value -= score * c;
+            }
+        }
+    }
+    removed
+        .iter()
+        .map(|removed| if total > 0.0 { removed / total } else { 0.0 })
+        .collect()
+}
+
+fn rebuild(space: &Space, embeddings: &[Vec<f64>]) -> Space {
+    let tokens: Vec<Token> = space
+        .tokens
+        .iter()
+        .zip(embeddings)
+        .map(|(token, embedding)| token.with_embedding(embedding.clone()))
+        .collect();
+    // still in the basis of the PCA, if any
+    Space {
+        pca: space.pca.clone(),
+        ..Space::new(tokens, None, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn space() -> Space {
+        // every token shares a large offset on the first dimension
+        let tokens: Vec<Token> = (0..40)
+            .map(|i| {
+                let angle = i as f64 * 0.9;
+                let mut embedding = vec![20.0 + angle.sin() * 0.1];
+                embedding.extend((0..9).map(|j| (angle * (j + 1) as f64).cos()));
+                Token::new("w".to_string(), i, 0, embedding)
+            })
+            .collect();
+        Space::new(tokens, None, None)
+    }
+
+    #[test]
+    fn test_rogue_dimensions() {
+        let space = space();
+        assert!(anisotropy(&space.tokens) > 0.5);
+        assert_eq!(rogue_dimensions(&space.tokens), vec![0]);
+
+        let (scaled, report) = Preprocessing::new(false, false, 0, true).apply(&space);
+        assert_eq!(report.rogue_dimensions, vec![0]);
+        assert!(anisotropy(&scaled.tokens) < report.anisotropy_before);
+    }
+
+    #[test]
+    fn test_center_standardize_and_remove_top() {
+        let space = space();
+        let (processed, report) = Preprocessing::new(true, true, 2, false).apply(&space);
+        assert_abs_diff_eq!(processed.space_center[0], 0.0, epsilon = 1e-9);
+        assert_eq!(report.removed_variance.len(), 2);
+        assert!(report.removed_variance[0] >= report.removed_variance[1]);
+        assert!(report.anisotropy_after.abs() < 0.1);
+
+        let (same, _) = Preprocessing::default().apply(&space);
+        assert_eq!(same.tokens[3].embedding, space.tokens[3].embedding);
+    }
+}
@@ -1,6 +1,8 @@
 use pyo3::pyclass;
 use serde::{Deserialize, Serialize};
 
+/// The words of one group, e.g. male words. A seed may be a phrase such as
+/// "single mother", matched on consecutive tokens of a line.
 #[pyclass]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubspaceSeeds {
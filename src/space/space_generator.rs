@@ -1,9 +1,12 @@
 use super::SpaceGenerator;
 use crate::embedding::models::Token;
 use crate::embedding::models::TokenOperators;
+use crate::space::center::CenterEstimator;
+use crate::space::matching::{MatchOptions, SeedMatcher};
+use crate::space::vector;
 use crate::space::SubspaceSeeds;
 use crate::util::pca::PCA;
-use nalgebra::{DMatrix, RowDVector};
+use crate::util::Message;
 
 #[derive(Clone, Debug)]
 pub struct Space {
@@ -11,9 +14,32 @@ pub struct Space {
     pub tokens: Vec<Token>,
     pub space_center: Vec<f64>,
     pub subspace_seed_words: Option<Vec<String>>,
+    /// The PCA the tokens were projected with, to project other spaces the same way
+    pub pca: Option<PCA>,
+}
+
+impl Space {
+    /// Estimate the center with another estimator than the mean
+    pub fn with_center(mut self, estimator: CenterEstimator, matching: &MatchOptions) -> Self {
+        self.space_center = estimator.center(&self.tokens, matching);
+        self
+    }
+
+    /// Project the tokens with a PCA fitted elsewhere
+    pub fn with_pca(self, pca: &PCA) -> Self {
+        let tokens = pca.transform_tokens(&self.tokens);
+        Space {
+            space_center: get_center(tokens.clone()),
+            tokens,
+            pca: Some(pca.clone()),
+            ..self
+        }
+    }
 }
 
 impl SpaceGenerator for Space {
+    /// Panics on an empty space: `Dataset::new` rejects empty inputs and every subspace
+    /// is checked by `SeedCoverage::check`, so the callers never pass one
     fn new<T: TokenOperators>(
         items: T,
         subspace_seeds: Option<SubspaceSeeds>,
@@ -28,17 +54,22 @@ impl SpaceGenerator for Space {
             panic!("The space is empty!");
         }
 
+        let pca = subspace_seeds
+            .is_none()
+            .then(|| pca(&tokens, pca_dimension))
+            .flatten();
         Space {
             space_name: subspace_seeds
                 .as_ref()
                 .map(|seeds| seeds.name.to_string())
                 .unwrap_or_else(|| "Global".to_string()),
-            tokens: subspace_seeds
+            tokens: pca
                 .as_ref()
-                .map(|_| tokens.clone())
-                .unwrap_or_else(|| pca(tokens.clone(), pca_dimension)),
+                .map(|pca| pca.transform_tokens(&tokens))
+                .unwrap_or_else(|| tokens.clone()),
             space_center: get_center(tokens.clone()),
             subspace_seed_words: subspace_seeds.map(|seeds| seeds.seeds),
+            pca,
         }
     }
 
@@ -47,11 +78,12 @@ impl SpaceGenerator for Space {
     }
 
     /// Find the words of interest in the space
-    fn find(&self, subspace_seed: &SubspaceSeeds) -> Vec<Token> {
+    fn find(&self, subspace_seed: &SubspaceSeeds, matching: &MatchOptions) -> Vec<Token> {
         find(
-            &self.tokens.clone(),
+            &self.tokens,
             &subspace_seed.seeds,
             subspace_seed.name.clone(),
+            matching,
         )
     }
 
@@ -64,10 +96,12 @@ impl SpaceGenerator for Space {
         get_std(self.tokens.clone())
     }
 
-    fn get_neutral_tokens(&self, exclude: Vec<String>) -> Vec<Token> {
+    fn get_neutral_tokens(&self, exclude: Vec<String>, matching: &MatchOptions) -> Vec<Token> {
+        // only the occurrences of a phrase are excluded, not each of its words
+        let excluded = SeedMatcher::new(&exclude, matching).mask(&self.tokens);
         let mut neutral_tokens: Vec<Token> = Vec::new();
-        for token in &self.tokens {
-            if !exclude.contains(&token.word) {
+        for (token, excluded) in self.tokens.iter().zip(excluded) {
+            if !excluded {
                 neutral_tokens.push(token.clone());
             }
         }
@@ -90,88 +124,37 @@ impl SpaceGenerator for Space {
     }
 }
 
-fn pca(tokens: Vec<Token>, pca_dimension: Option<usize>) -> Vec<Token> {
-    let n_components = match pca_dimension {
-        None => return tokens,
-        Some(x) => x,
-    };
-
+/// Fit a PCA on the tokens, unless they already have no more than `pca_dimension` dimensions
+fn pca(tokens: &[Token], pca_dimension: Option<usize>) -> Option<PCA> {
+    let n_components = pca_dimension?;
     if tokens[0].embedding.len() <= n_components {
-        return tokens;
+        return None;
     }
-
-    let mut pca_model = PCA::new(n_components);
-
-    let embeddings: DMatrix<f64> = DMatrix::from_rows(
-        &tokens
-            .iter()
-            .map(|token| RowDVector::from_vec(token.embedding.clone()))
-            .collect::<Vec<RowDVector<f64>>>(),
-    );
-
-    pca_model = pca_model.fit(embeddings.clone());
-
-    let transformed_embeddings = pca_model.transform(embeddings);
-
-    tokens
-        .iter()
-        .enumerate()
-        .map(|(i, token)| {
-            Token::new(
-                token.word.clone(),
-                token.line_num,
-                token.position,
-                transformed_embeddings.row(i).iter().cloned().collect(),
-            )
-        })
-        .collect()
+    Some(PCA::fit_tokens(n_components, tokens))
 }
 
+#[allow(dead_code)]
 fn get_std(tokens: Vec<Token>) -> Vec<f64> {
-    // calculate the stand deviation
-    let mut std: Vec<f64> = Vec::new();
-    for i in 0..tokens[0].embedding.len() {
-        let mut sum = 0.0;
-        for token in &tokens {
-            sum += token.embedding[i];
-        }
-        let mean = sum / tokens.len() as f64;
-        let mut sum_of_square = 0.0;
-        for token in &tokens {
-            sum_of_square += (token.embedding[i] - mean).powi(2);
-        }
-        std.push((sum_of_square / tokens.len() as f64).sqrt());
-    }
-    std
+    vector::std(&embeddings(&tokens))
 }
 
 fn get_center(tokens: Vec<Token>) -> Vec<f64> {
-    let mut center: Vec<f64> = Vec::new();
-
-    for i in 0..tokens[0].embedding.len() {
-        let sum: f64 = tokens.iter().map(|token| token.embedding[i]).sum();
-        center.push(sum / tokens.len() as f64);
-    }
+    vector::mean(&embeddings(&tokens))
+}
 
-    center
+fn embeddings(tokens: &[Token]) -> Vec<&[f64]> {
+    tokens.iter().map(|token| &token.embedding[..]).collect()
 }
 
 fn find(
-    space_tokens: &Vec<Token>,
-    passed_in_words: &Vec<String>,
+    space_tokens: &[Token],
+    passed_in_words: &[String],
     subspace_name: String,
+    matching: &MatchOptions,
 ) -> Vec<Token> {
-    let find_tokens: Vec<Token> = space_tokens
-        .iter()
-        .filter(|token| passed_in_words.contains(&token.word))
-        .cloned()
-        .collect();
-
-    println!(
-        "Subpace `{}`: The number of tokens found is {}",
-        subspace_name,
-        find_tokens.len()
-    );
+    let find_tokens = SeedMatcher::new(passed_in_words, matching).find(space_tokens);
+
+    Message::seeds_found(&subspace_name, find_tokens.len(), matching);
 
     find_tokens
 }
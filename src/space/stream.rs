@@ -0,0 +1,112 @@
+use crate::embedding::models::{Line, Token};
+use crate::space::matching::{MatchOptions, SeedMatcher};
+use crate::space::seeds::SubspaceSeeds;
+use crate::util::Message;
+
+/// The part of a stream kept in memory: only the tokens matching a seed
+pub struct SeedScan {
+    pub seed_tokens: Vec<Vec<Token>>,
+    pub num_of_tokens: usize,
+}
+
+/// Look up the seeds of every subspace in one pass over the lines
+pub fn scan_seeds<I: Iterator<Item = Line>>(
+    lines: I,
+    subspace_seeds: &[SubspaceSeeds],
+    matching: &MatchOptions,
+) -> SeedScan {
+    let matchers: Vec<SeedMatcher> = subspace_seeds
+        .iter()
+        .map(|subspace_seed| SeedMatcher::new(&subspace_seed.seeds, matching))
+        .collect();
+
+    let mut scan = SeedScan {
+        seed_tokens: vec![Vec::new(); subspace_seeds.len()],
+        num_of_tokens: 0,
+    };
+    for line in lines {
+        scan.num_of_tokens += line.tokens.len();
+        for (matcher, found) in matchers.iter().zip(scan.seed_tokens.iter_mut()) {
+            found.extend(matcher.find(&line.tokens));
+        }
+    }
+
+    for (subspace_seed, found) in subspace_seeds.iter().zip(&scan.seed_tokens) {
+        Message::seeds_found(&subspace_seed.name, found.len(), matching);
+    }
+
+    scan
+}
+
+/// Lazily drop the excluded words from a stream of lines
+pub fn neutral_tokens<I: Iterator<Item = Line>>(
+    lines: I,
+    exclude: Vec<String>,
+    matching: &MatchOptions,
+) -> impl Iterator<Item = Token> {
+    let matcher = SeedMatcher::new(&exclude, matching);
+    lines.flat_map(move |line| {
+        let excluded = matcher.mask(&line.tokens);
+        line.tokens
+            .into_iter()
+            .zip(excluded)
+            .filter(|(_, excluded)| !excluded)
+            .map(|(token, _)| token)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines() -> Vec<Line> {
+        let words = ["he", "is", "a", "nurse", "she", "is"];
+        vec![Line {
+            tokens: words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| Token::new(word.to_string(), i, 0, vec![i as f64]))
+                .collect(),
+            line_num: 0,
+        }]
+    }
+
+    #[test]
+    fn test_scan_seeds() {
+        let seeds = vec![
+            SubspaceSeeds::new("male".to_string(), vec!["he".to_string()]),
+            SubspaceSeeds::new("female".to_string(), vec!["she".to_string()]),
+        ];
+        let scan = scan_seeds(lines().into_iter(), &seeds, &MatchOptions::default());
+        assert_eq!(scan.num_of_tokens, 6);
+        assert_eq!(scan.seed_tokens[0].len(), 1);
+        assert_eq!(scan.seed_tokens[1][0].position, 4);
+    }
+
+    #[test]
+    fn test_neutral_tokens() {
+        let exclude = vec!["he".to_string(), "she".to_string()];
+        let words: Vec<String> =
+            neutral_tokens(lines().into_iter(), exclude, &MatchOptions::default())
+                .map(|token| token.word)
+                .collect();
+        assert_eq!(words, vec!["is", "a", "nurse", "is"]);
+    }
+
+    #[test]
+    fn test_case_folded_seeds() {
+        let seeds = vec![SubspaceSeeds::new(
+            "male".to_string(),
+            vec!["HE".to_string()],
+        )];
+        let matching = MatchOptions::new(true, false, false);
+        let scan = scan_seeds(lines().into_iter(), &seeds, &matching);
+        assert_eq!(scan.seed_tokens[0].len(), 1);
+
+        let words: Vec<String> =
+            neutral_tokens(lines().into_iter(), vec!["HE".to_string()], &matching)
+                .map(|token| token.word)
+                .collect();
+        assert_eq!(words.len(), 5);
+    }
+}
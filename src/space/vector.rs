@@ -0,0 +1,58 @@
+/// The mean of each dimension
+pub fn mean<E: AsRef<[f64]>>(embeddings: &[E]) -> Vec<f64> {
+    let mut center = vec![0.0; embeddings[0].as_ref().len()];
+    for embedding in embeddings {
+        for (c, x) in center.iter_mut().zip(embedding.as_ref()) {
+            *c += x;
+        }
+    }
+    center.iter().map(|c| c / embeddings.len() as f64).collect()
+}
+
+/// The standard deviation of each dimension
+pub fn std<E: AsRef<[f64]>>(embeddings: &[E]) -> Vec<f64> {
+    let mean = mean(embeddings);
+    let mut variance = vec![0.0; mean.len()];
+    for embedding in embeddings {
+        for ((v, x), m) in variance.iter_mut().zip(embedding.as_ref()).zip(&mean) {
+            *v += (x - m).powi(2);
+        }
+    }
+    variance
+        .iter()
+        .map(|v| (v / embeddings.len() as f64).sqrt())
+        .collect()
+}
+
+pub fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// The embedding scaled to unit length, a zero embedding is kept as is
+pub fn normalize(embedding: &[f64]) -> Vec<f64> {
+    let norm = dot(embedding, embedding).sqrt();
+    if norm == 0.0 {
+        return embedding.to_vec();
+    }
+    embedding.iter().map(|x| x / norm).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_mean_and_std() {
+        let embeddings = vec![vec![1.0, 2.0], vec![3.0, 2.0]];
+        assert_eq!(mean(&embeddings), vec![2.0, 2.0]);
+        assert_eq!(std(&embeddings), vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let unit = normalize(&[3.0, 4.0]);
+        assert_abs_diff_eq!(dot(&unit, &unit), 1.0, epsilon = 1e-12);
+        assert_eq!(normalize(&[0.0, 0.0]), vec![0.0, 0.0]);
+    }
+}
@@ -0,0 +1,105 @@
+use nalgebra::{DMatrix, DVector};
+
+/// Multinomial logistic regression trained by full batch gradient descent
+pub struct LogisticRegression {
+    weights: DMatrix<f64>,
+    bias: DVector<f64>,
+    n_classes: usize,
+    epochs: usize,
+    l2: f64,
+}
+
+impl LogisticRegression {
+    pub fn new(n_classes: usize, epochs: usize, l2: f64) -> Self {
+        LogisticRegression {
+            weights: DMatrix::zeros(0, 0),
+            bias: DVector::zeros(0),
+            n_classes,
+            epochs,
+            l2,
+        }
+    }
+
+    /// `x` has one sample per row, `y` their class in `0..n_classes`
+    pub fn fit(mut self, x: &DMatrix<f64>, y: &[usize]) -> Self {
+        let n_samples = x.nrows() as f64;
+        self.weights = DMatrix::zeros(self.n_classes, x.ncols());
+        self.bias = DVector::zeros(self.n_classes);
+
+        // the step is the inverse of a bound of the curvature of the loss
+        let mean_square_norm = x.norm_squared() / n_samples;
+        let step = 1.0 / (0.5 * (mean_square_norm + 1.0) + self.l2);
+
+        let mut one_hot = DMatrix::zeros(x.nrows(), self.n_classes);
+        for (i, &class) in y.iter().enumerate() {
+            one_hot[(i, class)] = 1.0;
+        }
+        for _ in 0..self.epochs {
+            let residual = self.probabilities(x) - &one_hot;
+            let gradient = residual.transpose() * x / n_samples + &self.weights * self.l2;
+            let bias_gradient = residual.row_sum().transpose() / n_samples;
+            self.weights -= gradient * step;
+            self.bias -= bias_gradient * step;
+        }
+        self
+    }
+
+    /// The softmax of the scores, one row per sample
+    fn probabilities(&self, x: &DMatrix<f64>) -> DMatrix<f64> {
+        let mut scores = x * self.weights.transpose();
+        for mut row in scores.row_iter_mut() {
+            row += self.bias.transpose();
+            let max = row.max();
+            row.apply(|score| *score = (*score - max).exp());
+            let sum = row.sum();
+            row /= sum;
+        }
+        scores
+    }
+
+    pub fn predict(&self, x: &DMatrix<f64>) -> Vec<usize> {
+        self.probabilities(x)
+            .row_iter()
+            .map(|row| row.transpose().argmax().0)
+            .collect()
+    }
+
+    pub fn accuracy(&self, x: &DMatrix<f64>, y: &[usize]) -> f64 {
+        let correct = self
+            .predict(x)
+            .iter()
+            .zip(y)
+            .filter(|(predicted, class)| predicted == class)
+            .count();
+        correct as f64 / y.len() as f64
+    }
+
+    /// One row of weights per class
+    pub fn weights(&self) -> &DMatrix<f64> {
+        &self.weights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logistic_regression() {
+        let x = DMatrix::from_row_slice(
+            6,
+            2,
+            &[
+                2.0, 0.3, 1.5, -0.2, 1.8, 0.1, -2.0, 0.2, -1.7, -0.1, -1.6, 0.4,
+            ],
+        );
+        let y = vec![0, 0, 0, 1, 1, 1];
+        let model = LogisticRegression::new(2, 200, 0.0).fit(&x, &y);
+        assert_eq!(model.accuracy(&x, &y), 1.0);
+        // the classes differ on the first feature only
+        let weights = model.weights();
+        assert!(
+            (weights[(0, 0)] - weights[(1, 0)]).abs() > (weights[(0, 1)] - weights[(1, 1)]).abs()
+        );
+    }
+}
@@ -2,5 +2,39 @@
 pub const TEMPLATE: &str = "[{prefix:>!20.green}] {bar:40.cyan/blue} {percent}% {binary_bytes_per_sec} [{elapsed_precise}]";
 pub const PROGRESS_CHARS: &str = "#>-";
 pub const FILE_READING: &str = "Reading file";
+#[allow(dead_code)]
 pub const TOKEN_GENERATING: &str = "Generating tokens";
 pub const SPACE_GENERATING: &str = "Generating space";
+
+// lines parsed together by the parallel readers
+pub const PARSE_BATCH_LINES: usize = 1024;
+
+// binary cache written next to the source file
+pub const CACHE_EXTENSION: &str = ".wfcache";
+
+// PCA written next to the debiased tokens
+pub const PCA_EXTENSION: &str = ".pca.json";
+
+// missing seeds listed in the coverage warning
+pub const MISSING_SEEDS_SHOWN: usize = 10;
+
+// iterative nullspace projection
+pub const INLP_ITERATIONS: usize = 10;
+pub const INLP_EPOCHS: usize = 500;
+pub const INLP_L2: f64 = 1e-4;
+pub const INLP_RANK_TOLERANCE: f64 = 1e-8;
+// seed tokens needed to hold out a fifth of the words for the accuracy
+pub const INLP_HOLD_OUT_FROM: usize = 10;
+
+// group center estimators
+pub const TRIM_FRACTION: f64 = 0.1;
+pub const GEOMETRIC_MEDIAN_ITERATIONS: usize = 100;
+pub const GEOMETRIC_MEDIAN_TOLERANCE: f64 = 1e-9;
+pub const MEDOID_SAMPLE: usize = 1000;
+
+// covariance of the space for the Mahalanobis distance
+pub const COVARIANCE_CHUNK_TOKENS: usize = 4096;
+pub const COVARIANCE_RIDGE: f64 = 1e-6;
+
+// a rogue dimension carries this many times an even share of the anisotropy
+pub const ROGUE_DIMENSION_FACTOR: f64 = 5.0;
@@ -1,12 +1,138 @@
+use crate::analyizer::calculator::Calculator;
+use crate::analyizer::metric::Metric;
+use crate::embedding::layer::LayerSelection;
+use crate::fio::reader::error::ReadError;
+use crate::space::center::CenterEstimator;
+use crate::space::coverage::SubspaceCoverage;
+use crate::space::matching::MatchOptions;
+use crate::space::preprocess::PreprocessReport;
+use crate::util::constant;
 use crate::util::Message;
 
 impl Message {
-    pub fn calculator_info(model_name: Option<String>, path: &str, pca_dimension: Option<usize>) {
-        println!("🔮 Model: {}", model_name.unwrap_or_else(|| path.to_string()));
+    pub fn calculator_info(
+        model_name: Option<String>,
+        path: &str,
+        pca_dimension: Option<usize>,
+        layer: Option<&LayerSelection>,
+    ) {
+        println!(
+            "🔮 Model: {}",
+            model_name.unwrap_or_else(|| path.to_string())
+        );
         println!("📚 Reading data from: {}", path);
         match pca_dimension {
             Some(dimension) => println!("📊 PCA Dimension: {}", dimension),
             None => println!("📊 PCA Dimension: None"),
         }
+        match layer {
+            Some(layer) => println!("🧅 Layer: {}", layer),
+            None => println!("🧅 Layer: all"),
+        }
+    }
+
+    pub fn scoring_info(center: &CenterEstimator, metric: &Metric) {
+        println!("🎯 Center: {}", center);
+        println!("📏 Metric: {}", metric);
+    }
+
+    pub fn seeds_found(subspace_name: &str, num_of_tokens: usize, matching: &MatchOptions) {
+        println!(
+            "Subpace `{}`: The number of tokens found is {} (matching: {})",
+            subspace_name, num_of_tokens, matching
+        );
+    }
+
+    pub fn seed_coverage(coverage: &SubspaceCoverage) {
+        let missing = coverage.missing();
+        if missing.is_empty() {
+            return;
+        }
+        let mut shown = missing
+            .iter()
+            .take(constant::MISSING_SEEDS_SHOWN)
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", ");
+        if missing.len() > constant::MISSING_SEEDS_SHOWN {
+            shown.push_str(", ...");
+        }
+        println!(
+            "⚠️  Subspace `{}`: {}/{} seeds found, missing: {}",
+            coverage.name,
+            coverage.counts.len() - missing.len(),
+            coverage.counts.len(),
+            shown
+        );
+    }
+
+    pub fn definitional_pairs(num_of_found: usize, num_of_pairs: usize) {
+        println!(
+            "🧭 Bias direction: {}/{} definitional pairs found",
+            num_of_found, num_of_pairs
+        );
+    }
+
+    pub fn debias_summary(before: &Calculator, after: &Calculator) {
+        println!(
+            "🧹 Bias before debiasing: {:.4}, after: {:.4}",
+            before.get_bias(),
+            after.get_bias()
+        );
+        if let (Some(before), Some(after)) = (
+            before.directional_bias.as_ref(),
+            after.directional_bias.as_ref(),
+        ) {
+            println!(
+                "🧹 Direct bias before debiasing: {:.4}, after: {:.4}",
+                before.direct_bias, after.direct_bias
+            );
+        }
+    }
+
+    pub fn inlp_iteration(iteration: usize, accuracy: f64, baseline: f64) {
+        println!(
+            "🧽 INLP iteration {}: classifier accuracy {:.4} (majority baseline {:.4})",
+            iteration, accuracy, baseline
+        );
+    }
+
+    pub fn preprocessing(report: &PreprocessReport) {
+        println!("🧪 Preprocessing: {}", report.preprocessing);
+        if !report.rogue_dimensions.is_empty() {
+            println!(
+                "🧪 Rogue dimensions scaled down: {:?}",
+                report.rogue_dimensions
+            );
+        }
+        if !report.removed_variance.is_empty() {
+            println!(
+                "🧪 Variance of the removed components: {:.4}",
+                report.removed_variance.iter().sum::<f64>()
+            );
+        }
+        println!(
+            "🧪 Average cosine similarity before: {:.4}, after: {:.4}",
+            report.anisotropy_before, report.anisotropy_after
+        );
+    }
+
+    pub fn skipped_lines(path: &str, errors: &[ReadError]) {
+        println!("⚠️  Skipped {} bad line(s) in {}:", errors.len(), path);
+        for error in errors {
+            println!("    {}", error);
+        }
+    }
+
+    pub fn cache_loaded(path: &str) {
+        println!("📦 Loading cached tokens from: {}", path);
+    }
+
+    pub fn cache_written(path: &str) {
+        println!("📦 Cached tokens to: {}", path);
+    }
+
+    pub fn cache_not_written(path: &str, error: &std::io::Error) {
+        println!("⚠️  Could not write the cache {}: {}", path, error);
     }
 }
@@ -1,3 +1,4 @@
+pub mod classifier;
 pub mod constant;
 pub mod help_message;
 pub mod pca;
@@ -1,6 +1,14 @@
+use crate::embedding::models::Token;
+use crate::fio::reader::error::ReadError;
 use crate::util::progress_bar::ProgressBar;
 use nalgebra::{DMatrix, RowDVector, SVD};
+use pyo3::{pyclass, pymethods, PyResult};
+use serde::{Deserialize, Serialize};
 
+/// A fitted PCA, kept so that other datasets can be projected on the same components
+#[allow(clippy::upper_case_acronyms)]
+#[pyclass]
+#[derive(Debug, Clone)]
 pub struct PCA {
     mean: Vec<f64>,
     components: DMatrix<f64>,
@@ -54,11 +62,87 @@ impl PCA {
         let svd = SVD::new(covariance_matrix, true, true);
         let u = svd.u.unwrap();
 
-        // Select the top n_components principal components and convert to DMatrix
-        self.components = u.columns(0, self.n_components).clone_owned();
+        // Select the top n_components principal components, the singular values are not sorted
+        let mut order: Vec<usize> = (0..svd.singular_values.len()).collect();
+        order.sort_by(|&a, &b| svd.singular_values[b].total_cmp(&svd.singular_values[a]));
+        self.components = u.select_columns(&order[..self.n_components]);
         self
     }
 
+    /// One column per principal component
+    pub fn components(&self) -> &DMatrix<f64> {
+        &self.components
+    }
+
+    /// The dimension of the embeddings it was fitted on
+    pub fn dimension(&self) -> usize {
+        self.mean.len()
+    }
+
+    pub fn fit_tokens(n_components: usize, tokens: &[Token]) -> Self {
+        PCA::new(n_components).fit(to_matrix(tokens))
+    }
+
+    /// The tokens with their embeddings projected, of `dimension()` dimensions
+    pub fn transform_tokens(&self, tokens: &[Token]) -> Vec<Token> {
+        let transformed = self.transform(to_matrix(tokens));
+        tokens
+            .iter()
+            .enumerate()
+            .map(|(i, token)| token.with_embedding(transformed.row(i).iter().cloned().collect()))
+            .collect()
+    }
+
+    /// One embedding projected, without the progress bars of `transform`
+    pub fn project(&self, embedding: &[f64]) -> Vec<f64> {
+        let centered = RowDVector::from_iterator(
+            embedding.len(),
+            embedding.iter().zip(&self.mean).map(|(x, m)| x - m),
+        );
+        (centered * &self.components).iter().cloned().collect()
+    }
+
+    pub fn to_file(&self, path: &str) -> Result<(), ReadError> {
+        let file = PcaFile {
+            mean: self.mean.clone(),
+            components: self
+                .components
+                .column_iter()
+                .map(|column| column.iter().cloned().collect())
+                .collect(),
+        };
+        let json = serde_json::to_string(&file).expect("a PCA serializes to JSON");
+        std::fs::write(path, json).map_err(|error| ReadError::io(path, error))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, ReadError> {
+        let text = std::fs::read_to_string(path).map_err(|error| ReadError::io(path, error))?;
+        let file: PcaFile = serde_json::from_str(&text).map_err(|e| ReadError::Parse {
+            path: path.to_string(),
+            line: e.line(),
+            offset: 0,
+            message: e.to_string(),
+        })?;
+        let dimension = file.mean.len();
+        if let Some(component) = file.components.iter().find(|c| c.len() != dimension) {
+            return Err(ReadError::Dimension {
+                path: path.to_string(),
+                line: 0,
+                expected: dimension,
+                found: component.len(),
+            });
+        }
+        Ok(PCA {
+            components: DMatrix::from_iterator(
+                dimension,
+                file.components.len(),
+                file.components.iter().flatten().cloned(),
+            ),
+            n_components: file.components.len(),
+            mean: file.mean,
+        })
+    }
+
     pub fn transform(&self, x: DMatrix<f64>) -> DMatrix<f64> {
         let mut pb = ProgressBar::new(x.nrows() as u64, "PCA Transform", true);
         let mean_vector = RowDVector::from_vec(self.mean.clone());
@@ -75,6 +159,51 @@ impl PCA {
     }
 }
 
+/// What `PCA::to_file` writes, one list per component
+#[derive(Serialize, Deserialize)]
+struct PcaFile {
+    mean: Vec<f64>,
+    components: Vec<Vec<f64>>,
+}
+
+fn to_matrix(tokens: &[Token]) -> DMatrix<f64> {
+    DMatrix::from_rows(
+        &tokens
+            .iter()
+            .map(|token| RowDVector::from_vec(token.embedding.clone()))
+            .collect::<Vec<RowDVector<f64>>>(),
+    )
+}
+
+// Expose to Python
+#[pymethods]
+impl PCA {
+    /// Write the model as JSON, to project other datasets with `load_pca`
+    fn save(&self, path: &str) -> PyResult<()> {
+        Ok(self.to_file(path)?)
+    }
+
+    pub(crate) fn get_n_components(&self) -> usize {
+        self.n_components
+    }
+
+    fn get_dimension(&self) -> usize {
+        self.dimension()
+    }
+
+    fn get_mean(&self) -> Vec<f64> {
+        self.mean.clone()
+    }
+
+    /// One list of `get_dimension()` values per component
+    fn get_components(&self) -> Vec<Vec<f64>> {
+        self.components
+            .column_iter()
+            .map(|column| column.iter().cloned().collect())
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -99,4 +228,43 @@ mod tests {
         let x_transformed_expected = DMatrix::from_row_slice(3, 1, &[-2.82842712, 0.0, 2.82842712]);
         assert_abs_diff_eq!(x_transformed, x_transformed_expected, epsilon = 1e-6);
     }
+
+    #[test]
+    fn test_components_follow_the_variance() {
+        // the variance grows with the dimension, the SVD does not sort it
+        let x = DMatrix::from_row_slice(
+            4,
+            3,
+            &[
+                0.1, 1.0, 10.0, 0.1, -1.0, -10.0, -0.1, 1.0, -10.0, -0.1, -1.0, 10.0,
+            ],
+        );
+        let components = PCA::new(2).fit(x).components().clone();
+        assert_abs_diff_eq!(components[(2, 0)].abs(), 1.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(components[(1, 1)].abs(), 1.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_pca_file_roundtrip() {
+        let x = DMatrix::from_row_slice(
+            4,
+            3,
+            &[1.0, 2.0, 0.5, 3.0, 4.0, 0.1, 5.0, 7.0, 0.3, 0.0, 1.0, 0.2],
+        );
+        let pca = PCA::new(2).fit(x.clone());
+        let path = std::env::temp_dir()
+            .join(format!("wafflecone-pca-{}.json", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        pca.to_file(&path).unwrap();
+        let loaded = PCA::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.dimension(), 3);
+        let transformed = pca.transform(x.clone());
+        assert_abs_diff_eq!(loaded.transform(x), transformed, epsilon = 1e-12);
+        // one embedding projected alone, as when streaming
+        let projected = RowDVector::from_vec(loaded.project(&[3.0, 4.0, 0.1]));
+        assert_abs_diff_eq!(projected, transformed.row(1).clone_owned(), epsilon = 1e-12);
+    }
 }
@@ -1,6 +1,8 @@
 use crate::util::constant;
 use indicatif;
 
+/// Cheap to clone, the clones share the same bar
+#[derive(Clone)]
 pub struct ProgressBar {
     bar: Option<indicatif::ProgressBar>,
 }
@@ -5,13 +5,14 @@ use std::thread;
 
 pub struct Web {
     pub port: u16,
+    #[allow(dead_code)]
     pub version: String,
 }
 
 impl Web {
     pub fn new(port: u16) -> Self {
         Web {
-            port: port,
+            port,
             version: env!("CARGO_PKG_VERSION").to_string(),
         }
     }
@@ -1,18 +1,256 @@
+class ParseError(ValueError):
+    """A line of the input file is not valid."""
+
+class DimensionError(ValueError):
+    """The embeddings of the input file do not all have the same dimension."""
+
+class SeedCoverageError(ValueError):
+    """A subspace has no seed in the data, or fewer than `min_coverage`."""
+
 def version():
     """Print the version of the package."""
 
 def calculator(
-    path: str,
+    path: str | list[str],  # a file, a directory, a glob pattern such as `part-*.json`, or a list of them
     subspace_seeds: list[dict[str, list[str]]],
     exclude_words: list[str] = None,  # words to exclude from tokens
     user_friendly: bool = None,
     pca_dimension: int = None,
     model_name: str = None,
+    layer: int | list[int] | str = None,  # layer index, layers to concatenate, "last", "concat" or "mean"
+    streaming: bool = None,  # read the file lazily instead of loading it into memory, in two passes: regular files only, not pipes
+    skip_bad_lines: bool = None,  # skip and report malformed lines instead of raising
+    reader: str = None,  # "conceptx", "numpy", "word2vec", "word2vec_binary" or "glove", guessed from the file by default
+    vocab_path: str = None,  # token list of a numpy array or a directory of them, `<path>.tsv` by default
+    normalizer: "Normalizer" = None,  # subword marker handling, see `new_normalizer`
+    subword_pooling: str = None,  # merge subword pieces into whole words: "mean", "first", "last" or "max"
+    matching: "MatchOptions" = None,  # how seeds and exclusions match tokens, see `new_match_options`
+    threads: int = None,  # threads parsing the file, all cores by default
+    cache: bool = None,  # reuse a binary cache of the parsed file, written next to it as `<path>.wfcache`
+    min_coverage: float = None,  # share of the seeds of each subspace that must occur, e.g. 0.5
+    definitional_pairs: list[tuple[str, str]] = None,  # e.g. [("he", "she"), ("man", "woman")], adds the direct bias
+    bias_dimension: int = None,  # principal components of the pair differences kept, 1 by default
+    center: str = None,  # group centers: "mean" (default), "balanced" (each word weighs the same), "trimmed", "geometric_median" or "medoid"
+    metric: str = None,  # token to group center: "cosine" (default), "euclidean", "mahalanobis" (not with streaming), "dot" or "angular"
+    preprocessing: "Preprocessing" = None,  # anisotropy correction of the space, see `new_preprocessing` (not with streaming)
+    pca_model: "PCA" = None,  # a fitted PCA, see `load_pca` and `Dataset.fit_pca`, instead of pca_dimension
 ) -> "Calculator":
     """Print the calculator."""
 
+def calculator_from_embeddings(
+    embeddings: "numpy.ndarray | list[list[float]]",  # (tokens, dim) or (tokens, layers, dim)
+    words: list[str],
+    subspace_seeds: list[dict[str, list[str]]],
+    line_nums: list[int] = None,  # every token is its own line by default
+    positions: list[int] = None,
+    exclude_words: list[str] = None,  # words to exclude from tokens
+    pca_dimension: int = None,
+    model_name: str = None,
+    layer: int | list[int] | str = None,  # layer index, layers to concatenate, "last", "concat" or "mean"
+    normalizer: "Normalizer" = None,  # subword marker handling, see `new_normalizer`
+    subword_pooling: str = None,  # merge subword pieces into whole words: "mean", "first", "last" or "max"
+    matching: "MatchOptions" = None,  # how seeds and exclusions match tokens, see `new_match_options`
+    min_coverage: float = None,  # share of the seeds of each subspace that must occur, e.g. 0.5
+    definitional_pairs: list[tuple[str, str]] = None,  # e.g. [("he", "she"), ("man", "woman")], adds the direct bias
+    bias_dimension: int = None,  # principal components of the pair differences kept, 1 by default
+    center: str = None,  # group centers: "mean" (default), "balanced" (each word weighs the same), "trimmed", "geometric_median" or "medoid"
+    metric: str = None,  # token to group center: "cosine" (default), "euclidean", "mahalanobis" (not with streaming), "dot" or "angular"
+    preprocessing: "Preprocessing" = None,  # anisotropy correction of the space, see `new_preprocessing`
+    pca_model: "PCA" = None,  # a fitted PCA, see `load_pca` and `Dataset.fit_pca`, instead of pca_dimension
+) -> "Calculator":
+    """Compute the bias of embeddings already in memory."""
+
 def new_subspace_seeds(name: str, seeds: list[str]) -> "SubspaceSeed":
-    """Create a new subspace seed."""
+    """Create a new subspace seed. A seed may be a phrase such as "single mother",
+    matched on consecutive tokens of a line."""
+
+def load_subspace_seeds(
+    path: str,
+    format: str = None,  # "json", "toml" or "text", guessed from the extension by default
+) -> list["SubspaceSeed"]:
+    """Load the groups of a seed file.
+
+    JSON: `{"male": ["he", ...], "female": [...]}` or a list of `{"name": ..., "seeds": [...]}`.
+    TOML: `male = ["he", ...]`, one key per group.
+    Text: a `[male]` header, then one seed per line; `#` starts a comment."""
+
+def builtin_subspace_seeds(name: str) -> list["SubspaceSeed"]:
+    """The groups of a lexicon shipped with the package, e.g. "gender/binary-v1"."""
+
+def list_lexicons() -> list[str]:
+    """The names of the built-in lexicons."""
+
+def new_normalizer(
+    preset: str = None,  # "legacy" (default), "wordpiece", "bpe", "sentencepiece" or "none"
+    rules: list[tuple[str, str]] = None,  # literal (pattern, replacement) pairs
+    lowercase: bool = None,
+    nfkc: bool = None,  # Unicode NFKC normalization
+) -> "Normalizer":
+    """Create a token normalizer."""
+
+def new_match_options(
+    case_fold: bool = None,  # "He" matches the seed "he"
+    nfkc: bool = None,  # Unicode NFKC normalization, e.g. full width letters
+    strip_accents: bool = None,  # "fiancée" matches the seed "fiancee"
+) -> "MatchOptions":
+    """Create the options comparing tokens to seeds and excluded words, exact by default."""
+
+def new_preprocessing(
+    center: bool = None,  # subtract the mean of the space
+    standardize: bool = None,  # divide each dimension by its standard deviation
+    remove_top: int = None,  # dominant principal components removed, "all-but-the-top"
+    rogue_dimensions: bool = None,  # scale down the dimensions dominating the cosine similarity
+) -> "Preprocessing":
+    """Create the anisotropy correction applied to the space before the bias is computed, none by default."""
+
+def load_pca(path: str) -> "PCA":
+    """Load a PCA written by `PCA.save`, to project other datasets on the same components."""
 
 def visualize(port: int):
     """Visualize the calculator with web interface."""
+
+def layer_profile(
+    path: str | list[str],  # a file, a directory, a glob pattern such as `part-*.json`, or a list of them
+    subspace_seeds: list[dict[str, list[str]]],
+    exclude_words: list[str] = None,  # words to exclude from tokens
+    user_friendly: bool = None,
+    pca_dimension: int = None,
+    model_name: str = None,
+    top_k: int = None,  # number of most biased tokens kept per layer
+    skip_bad_lines: bool = None,  # skip and report malformed lines instead of raising
+    reader: str = None,  # "conceptx", "numpy", "word2vec", "word2vec_binary" or "glove", guessed from the file by default
+    vocab_path: str = None,  # token list of a numpy array or a directory of them, `<path>.tsv` by default
+    normalizer: "Normalizer" = None,  # subword marker handling, see `new_normalizer`
+    subword_pooling: str = None,  # merge subword pieces into whole words: "mean", "first", "last" or "max"
+    matching: "MatchOptions" = None,  # how seeds and exclusions match tokens, see `new_match_options`
+    threads: int = None,  # threads parsing the file, all cores by default
+    cache: bool = None,  # reuse a binary cache of the parsed file, written next to it as `<path>.wfcache`
+    min_coverage: float = None,  # share of the seeds of each subspace that must occur, e.g. 0.5
+    center: str = None,  # group centers: "mean" (default), "balanced" (each word weighs the same), "trimmed", "geometric_median" or "medoid"
+    metric: str = None,  # token to group center: "cosine" (default), "euclidean", "mahalanobis" (not with streaming), "dot" or "angular"
+    preprocessing: "Preprocessing" = None,  # anisotropy correction of the space, see `new_preprocessing`
+    pca_model: "PCA" = None,  # a fitted PCA, see `load_pca` and `Dataset.fit_pca`, instead of pca_dimension
+    layers: list[int] = None,  # layer indices to profile, all layers of the file by default
+) -> "LayerProfile":
+    """Compute the bias of every layer, reading the file only once.
+
+    Raises a ValueError when the file has no layer column or none of `layers` is in it."""
+
+def dataset(
+    path: str | list[str],  # a file, a directory, a glob pattern such as `part-*.json`, or a list of them
+    user_friendly: bool = None,
+    pca_dimension: int = None,
+    model_name: str = None,
+    layer: int | list[int] | str = None,  # layer index, layers to concatenate, "last", "concat" or "mean"
+    skip_bad_lines: bool = None,  # skip and report malformed lines instead of raising
+    reader: str = None,  # "conceptx", "numpy", "word2vec", "word2vec_binary" or "glove", guessed from the file by default
+    vocab_path: str = None,  # token list of a numpy array or a directory of them, `<path>.tsv` by default
+    normalizer: "Normalizer" = None,  # subword marker handling, see `new_normalizer`
+    subword_pooling: str = None,  # merge subword pieces into whole words: "mean", "first", "last" or "max"
+    threads: int = None,  # threads parsing the file, all cores by default
+    cache: bool = None,  # reuse a binary cache of the parsed file, written next to it as `<path>.wfcache`
+    preprocessing: "Preprocessing" = None,  # anisotropy correction of the space, see `new_preprocessing`
+    pca_model: "PCA" = None,  # a fitted PCA, see `load_pca` and `Dataset.fit_pca`, instead of pca_dimension
+) -> "Dataset":
+    """Load the file once, to build calculators for many seed lists."""
+
+class Dataset:
+    def calculator(
+        self,
+        subspace_seeds: list[dict[str, list[str]]],
+        exclude_words: list[str] = None,  # words to exclude from tokens
+        matching: "MatchOptions" = None,  # how seeds and exclusions match tokens, see `new_match_options`
+        min_coverage: float = None,  # share of the seeds of each subspace that must occur, e.g. 0.5
+        definitional_pairs: list[tuple[str, str]] = None,  # e.g. [("he", "she"), ("man", "woman")], adds the direct bias
+        bias_dimension: int = None,  # principal components of the pair differences kept, 1 by default
+        center: str = None,  # group centers: "mean" (default), "balanced" (each word weighs the same), "trimmed", "geometric_median" or "medoid"
+        metric: str = None,  # token to group center: "cosine" (default), "euclidean", "mahalanobis" (not with streaming), "dot" or "angular"
+    ) -> "Calculator":
+        """Compute the bias for the seeds, without reading the file again."""
+    def debias(
+        self,
+        subspace_seeds: list[dict[str, list[str]]],
+        definitional_pairs: list[tuple[str, str]],  # e.g. [("he", "she"), ("man", "woman")], the direction removed
+        bias_dimension: int = None,  # principal components of the pair differences removed, 1 by default
+        exclude_words: list[str] = None,  # words to exclude from tokens
+        matching: "MatchOptions" = None,  # how seeds and exclusions match tokens, see `new_match_options`
+        min_coverage: float = None,  # share of the seeds of each subspace that must occur, e.g. 0.5
+        output_path: str = None,  # write the debiased tokens (word, line, position, source, embedding) to this file, and the PCA, if any, to `<path>.pca.json`
+        center: str = None,  # group centers, as in `calculator`
+        metric: str = None,  # token to group center, as in `calculator`
+    ) -> "DebiasResult":
+        """Hard debias (neutralize and equalize) the tokens, then compute the bias again."""
+    def inlp(
+        self,
+        subspace_seeds: list[dict[str, list[str]]],  # the groups to tell apart, at least two
+        iterations: int = None,  # classifiers trained at most, 10 by default
+        matching: "MatchOptions" = None,  # how seeds match tokens, see `new_match_options`
+        min_coverage: float = None,  # share of the seeds of each subspace that must occur, e.g. 0.5
+    ) -> "InlpResult":
+        """Iterative nullspace projection: erase what a linear classifier learns to tell the groups apart."""
+    def get_model_name(self) -> str: ...
+    def get_num_lines(self) -> int: ...
+    def get_num_tokens(self) -> int: ...
+    def get_dimension(self) -> int: ...
+    def get_pca_dimension(self) -> int | None: ...
+    def get_sources(self) -> list[str]:
+        """The files read, in order."""
+    def get_preprocess_report(self) -> "PreprocessReport | None":
+        """What the anisotropy correction did to the space."""
+    def get_pca(self) -> "PCA | None":
+        """The PCA the tokens were projected with."""
+    def fit_pca(
+        self,
+        n_components: int,
+        subspace_seeds: list["SubspaceSeed"] = None,  # their words are left out of the fit
+        exclude_words: list[str] = None,  # words left out of the fit
+        matching: "MatchOptions" = None,  # how words are compared, see `new_match_options`
+    ) -> "PCA":
+        """Fit a PCA on this dataset, e.g. on its neutral tokens only, to project other datasets."""
+    def project(self, pca: "PCA") -> "Dataset":
+        """The same dataset projected with a PCA fitted elsewhere."""
+    def locate(self, word: str) -> list[tuple[str, int, int]]:
+        """The (source file, line number, position) of every occurrence of the word."""
+
+class SeedCoverage:
+    def get_counts(self) -> dict[str, dict[str, int]]:
+        """The number of occurrences of each seed, per subspace."""
+    def get_missing(self) -> dict[str, list[str]]:
+        """The seeds that never occur, per subspace."""
+    def get_coverage(self) -> dict[str, float]:
+        """The share of seeds found at least once, per subspace."""
+
+class DebiasResult:
+    def get_before(self) -> "Calculator": ...
+    def get_after(self) -> "Calculator": ...
+    def get_dataset(self) -> "Dataset":
+        """The debiased tokens, to build more calculators."""
+
+class InlpResult:
+    def get_projection(self) -> list[list[float]]:
+        """The (dimension, dimension) projection applied to the embeddings."""
+    def get_accuracy(self) -> list[float]:
+        """The held out accuracy of the classifier of each iteration."""
+    def get_baseline(self) -> float:
+        """The accuracy of always predicting the largest group."""
+    def get_rank(self) -> int:
+        """The number of dimensions removed."""
+    def get_dataset(self) -> "Dataset":
+        """The projected tokens, to build more calculators."""
+
+class PreprocessReport:
+    def get_steps(self) -> str: ...
+    def get_rogue_dimensions(self) -> list[int]: ...
+    def get_removed_variance(self) -> list[float]:
+        """The share of the variance carried by each removed principal component."""
+    def get_anisotropy_before(self) -> float:
+        """The average cosine similarity between two different tokens, before the correction."""
+    def get_anisotropy_after(self) -> float: ...
+
+class PCA:
+    def save(self, path: str):
+        """Write the model as JSON, to be loaded with `load_pca`."""
+    def get_n_components(self) -> int: ...
+    def get_dimension(self) -> int: ...
+    def get_mean(self) -> list[float]: ...
+    def get_components(self) -> list[list[float]]: ...